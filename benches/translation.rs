@@ -0,0 +1,69 @@
+// Simple benchmark of the output and argument translation.
+// Run with `cargo bench --features bench`.
+
+extern crate wslgit;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use wslgit::{translate_path_to_unix, translate_path_to_win};
+
+const ITERATIONS: u32 = 200;
+
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    // warm up
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!("{:<36} {:>10.2?}/iter", name, elapsed / ITERATIONS);
+}
+
+fn main() {
+    let mut output = Vec::new();
+    for i in 0..10000 {
+        output.extend_from_slice(
+            format!("/mnt/c/Users/me/repo/src/file{}.rs\n", i).as_bytes());
+    }
+    let mut plain = Vec::new();
+    for i in 0..10000 {
+        plain.extend_from_slice(
+            format!(" M src/file{}.rs\n", i).as_bytes());
+    }
+    let args: Vec<String> = (0..10000)
+        .map(|i| format!("--option{}", i))
+        .collect();
+    // e.g. `git add` with many files, skipped by the check for a colon
+    // or a backslash
+    let plain_paths: Vec<String> = (0..10000)
+        .map(|i| format!("src/file{}.rs", i))
+        .collect();
+    // with a colon, but not a path, so that all checks are run
+    let revisions: Vec<String> = (0..10000)
+        .map(|i| format!("HEAD~{}:src/file.rs", i))
+        .collect();
+
+    bench("translate_path_to_win (paths)", || {
+        black_box(translate_path_to_win(black_box(&output)));
+    });
+    bench("translate_path_to_win (no paths)", || {
+        black_box(translate_path_to_win(black_box(&plain)));
+    });
+    bench("translate_path_to_unix (no match)", || {
+        for arg in &args {
+            black_box(translate_path_to_unix(black_box(arg)));
+        }
+    });
+    bench("translate_path_to_unix (plain paths)", || {
+        for arg in &plain_paths {
+            black_box(translate_path_to_unix(black_box(arg)));
+        }
+    });
+    bench("translate_path_to_unix (revisions)", || {
+        for arg in &revisions {
+            black_box(translate_path_to_unix(black_box(arg)));
+        }
+    });
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+
+// Settings detected by starting WSL are stored per distribution, so that
+// they are only detected again after some time, or when the WSLGIT_*
+// environment variables are changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    // hash of the WSLGIT_* environment variables at the time of detection
+    pub env: u64,
+    // seconds since the Unix epoch
+    pub time: u64,
+    // detected settings by name, e.g. `mount_root`
+    pub values: BTreeMap<String, String>,
+}
+
+// one day, as the configuration of WSL is rarely changed
+pub const TTL_SECS: u64 = 24 * 60 * 60;
+
+const CACHE_FILE_NAME: &str = "cache.json";
+
+pub fn cache_path() -> Option<PathBuf> {
+    env::var_os("LOCALAPPDATA")
+        .map(|local| Path::new(&local).join("wslgit").join(CACHE_FILE_NAME))
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+pub fn env_hash<I>(vars: I) -> u64
+        where I: IntoIterator<Item = (String, String)> {
+    let mut vars: Vec<(String, String)> = vars.into_iter()
+        .filter(|(name, _)| name.starts_with("WSLGIT_"))
+        .collect();
+    vars.sort();
+    let mut hasher = DefaultHasher::new();
+    vars.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c)
+        }
+    }
+    unescaped
+}
+
+// The file is written as JSON with one line per distribution. It is only
+// read by wslgit, so only this format is recognized; anything else is
+// ignored and detected again.
+fn parse(text: &str) -> BTreeMap<String, Entry> {
+    lazy_static! {
+        static ref ENTRY_RE: Regex = Regex::new(
+            r#"(?m)^\s*"((?:[^"\\]|\\.)*)": \{(.*)\},?$"#)
+            .expect("Failed to compile cache entry regex");
+        static ref VALUE_RE: Regex = Regex::new(
+            r#""((?:[^"\\]|\\.)*)": (?:"((?:[^"\\]|\\.)*)"|(\d+))"#)
+            .expect("Failed to compile cache value regex");
+    }
+    ENTRY_RE.captures_iter(text)
+        .filter_map(|caps| {
+            let mut values = BTreeMap::new();
+            let (mut env, mut time) = (None, None);
+            for value in VALUE_RE.captures_iter(&caps[2]) {
+                match (&value[1], value.get(2), value.get(3)) {
+                    ("env", _, Some(n)) => env = n.as_str().parse().ok(),
+                    ("time", _, Some(n)) => time = n.as_str().parse().ok(),
+                    (name, Some(s), _) => {
+                        values.insert(unescape(name), unescape(s.as_str()));
+                    }
+                    _ => {}
+                }
+            }
+            Some((unescape(&caps[1]), Entry { env: env?, time: time?, values }))
+        })
+        .collect()
+}
+
+fn format(entries: &BTreeMap<String, Entry>) -> String {
+    let lines: Vec<String> = entries.iter()
+        .map(|(distro, entry)| {
+            let mut fields = vec![
+                format!("\"env\": {}", entry.env),
+                format!("\"time\": {}", entry.time)];
+            fields.extend(entry.values.iter().map(|(name, value)| {
+                format!("\"{}\": \"{}\"", escape(name), escape(value))
+            }));
+            format!("  \"{}\": {{{}}}", escape(distro), fields.join(", "))
+        })
+        .collect();
+    format!("{{\n{}\n}}\n", lines.join(",\n"))
+}
+
+pub fn read(path: &Path) -> BTreeMap<String, Entry> {
+    fs::read_to_string(path).map(|text| parse(&text)).unwrap_or_default()
+}
+
+pub fn write(path: &Path, entries: &BTreeMap<String, Entry>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format(entries))
+}
+
+pub fn is_valid(entry: &Entry, env: u64, now: u64) -> bool {
+    entry.env == env && now >= entry.time && now - entry.time < TTL_SECS
+}
+
+pub fn get_or_detect<F>(path: &Path, distro: &str, name: &str, env: u64, now: u64,
+                        detect: F) -> Option<String>
+        where F: FnOnce() -> Option<String> {
+    // the default distribution is stored with an empty name
+    let mut entries = read(path);
+    let entry = entries.get(distro).filter(|entry| is_valid(entry, env, now));
+    if let Some(value) = entry.and_then(|entry| entry.values.get(name)) {
+        return Some(value.clone());
+    }
+    let value = detect()?;
+    if entry.is_none() {
+        // expired settings are all detected again
+        entries.insert(distro.to_owned(), Entry { env, time: now, values: BTreeMap::new() });
+    }
+    if let Some(entry) = entries.get_mut(distro) {
+        entry.values.insert(name.to_owned(), value.clone());
+    }
+    // without cache, the value is just detected again next time
+    let _ = write(path, &entries);
+    Some(value)
+}
+
+
+#[test]
+fn cache_round_trip() {
+    let dir = env::temp_dir().join(format!("wslgit-cache-{}", std::process::id()));
+    let path = dir.join(CACHE_FILE_NAME);
+    let values = |values: &[(&str, &str)]| -> BTreeMap<String, String> {
+        values.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect()
+    };
+    let mut entries = BTreeMap::new();
+    entries.insert(String::new(), Entry {
+        env: 1, time: 2, values: values(&[("mount_root", "/mnt/"), ("wsl_version", "2")])
+    });
+    entries.insert("My \"Distro\"".to_owned(), Entry {
+        env: 3, time: 4, values: values(&[("mount_root", "C:\\x")])
+    });
+    write(&path, &entries).unwrap();
+    let read_entries = read(&path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(read_entries, entries);
+    assert!(parse("not json").is_empty());
+}
+
+#[test]
+fn cache_expiry() {
+    let dir = env::temp_dir().join(format!("wslgit-cache-ttl-{}", std::process::id()));
+    let path = dir.join(CACHE_FILE_NAME);
+    let mut detected = 0;
+    let mut detect = |root: &str| {
+        detected += 1;
+        Some(root.to_owned())
+    };
+    let root = get_or_detect(&path, "Debian", "mount_root", 7, 1000, 
+                             || detect("/"));
+    assert_eq!(root.as_deref(), Some("/"));
+    // cache hit, nothing is detected
+    let root = get_or_detect(&path, "Debian", "mount_root", 7, 1000 + TTL_SECS - 1,
+                             || detect("/x"));
+    assert_eq!(root.as_deref(), Some("/"));
+    // expired
+    let root = get_or_detect(&path, "Debian", "mount_root", 7, 1000 + TTL_SECS,
+                             || detect("/y"));
+    assert_eq!(root.as_deref(), Some("/y"));
+    // changed environment variables
+    let root = get_or_detect(&path, "Debian", "mount_root", 8, 1000 + TTL_SECS,
+                             || detect("/z"));
+    assert_eq!(root.as_deref(), Some("/z"));
+    // other settings are added to the entry
+    let version = get_or_detect(&path, "Debian", "wsl_version", 8, 1000 + TTL_SECS,
+                                || Some("2".to_owned()));
+    assert_eq!(version.as_deref(), Some("2"));
+    let entries = read(&path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(detected, 3);
+    assert_eq!(entries["Debian"].values.len(), 2);
+    assert_eq!(entries["Debian"].time, 1000 + TTL_SECS);
+}
+
+#[test]
+fn env_var_hash() {
+    let vars = |vars: &[(&str, &str)]| -> Vec<(String, String)> {
+        vars.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect()
+    };
+    assert_eq!(env_hash(vars(&[("WSLGIT_DISTRO", "a"), ("PATH", "x")])),
+               env_hash(vars(&[("PATH", "y"), ("WSLGIT_DISTRO", "a")])));
+    assert_ne!(env_hash(vars(&[("WSLGIT_DISTRO", "a")])),
+               env_hash(vars(&[("WSLGIT_DISTRO", "b")])));
+}
@@ -0,0 +1,489 @@
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Settings from the optional configuration file, which are overridden by
+// the WSLGIT_* environment variables. Settings that are neither in the file
+// nor in the environment are None and use the built-in defaults.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub distro: Option<String>,
+    pub user: Option<String>,
+    pub shell: Option<String>,
+    pub mount_root: Option<String>,
+    pub no_translate: Option<Vec<String>>,
+    pub force_translate: Option<bool>,
+    pub convert_relative_slashes: Option<bool>,
+    pub stdin: Option<String>,
+    pub wsl_exe: Option<String>,
+    pub merge_stderr: Option<bool>,
+    pub direct_cmds: Option<Vec<String>>,
+    pub pager: Option<String>,
+    pub wsl_args: Option<Vec<String>>,
+    pub output_eol: Option<String>,
+    pub start_retries: Option<u32>,
+    pub output_separator: Option<String>,
+    pub literal_options: Option<Vec<String>>,
+    pub translate: Option<String>,
+    pub drives: Option<String>,
+    pub max_capture: Option<usize>,
+    pub fallback_git: Option<String>,
+    pub debug: Option<bool>,
+    pub dry_run: Option<bool>,
+}
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+pub fn get() -> &'static Config {
+    lazy_static! {
+        static ref CONFIG: Config = load();
+    }
+    &CONFIG
+}
+
+fn load() -> Config {
+    let file_config = config_paths().iter()
+        .find(|path| path.is_file())
+        .and_then(|path| match load_file(path) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                eprintln!("wslgit: ignoring config file {}: {}",
+                          path.display(), err);
+                None
+            }
+        })
+        .unwrap_or_default();
+    let (env_config, errors) = from_env(|name| env::var(name).ok());
+    for err in errors {
+        eprintln!("wslgit: ignoring {}", err);
+    }
+    env_config.merge(file_config)
+}
+
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(appdata) = env::var_os("APPDATA") {
+        paths.push(Path::new(&appdata).join("wslgit").join(CONFIG_FILE_NAME));
+    }
+    if let Some(exe_dir) = env::current_exe().ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        paths.push(exe_dir.join(CONFIG_FILE_NAME));
+    }
+    paths
+}
+
+fn load_file(path: &Path) -> io::Result<Config> {
+    let text = fs::read_to_string(path)?;
+    parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn parse_flag(flag: &str) -> bool {
+    flag == "true" || flag == "1"
+}
+
+pub fn parse_cmd_list(list: &str) -> Vec<String> {
+    // comma separated, surrounding whitespace and empty entries are ignored;
+    // git commands are case sensitive, so entries are kept as they are
+    list.split(',')
+        .map(str::trim)
+        .filter(|cmd| !cmd.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+pub fn parse_args(args: &str) -> Vec<String> {
+    // separated by whitespace, without any quoting
+    args.split_whitespace().map(str::to_owned).collect()
+}
+
+fn parse_number<T: FromStr>(number: &str) -> Option<T> {
+    // only digits, no sign, like the integers in the configuration file
+    let number = number.trim();
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+fn number_var<T: FromStr>(name: &str, value: Option<String>, errors: &mut Vec<String>)
+        -> Option<T> {
+    let value = value?;
+    let number = parse_number(&value);
+    if number.is_none() {
+        errors.push(format!("{}: `{}` is not a number", name, value));
+    }
+    number
+}
+
+// Values which are ignored, e.g. because they are not numbers, are returned
+// as errors, which load() reports.
+fn from_env<F>(lookup: F) -> (Config, Vec<String>)
+        where F: Fn(&str) -> Option<String> {
+    let non_empty = |name| lookup(name).filter(|value| !value.is_empty());
+    let mut errors = Vec::new();
+    let config = Config {
+        distro: non_empty("WSLGIT_DISTRO"),
+        user: non_empty("WSLGIT_USER"),
+        shell: non_empty("WSLGIT_SHELL"),
+        mount_root: lookup("WSLGIT_MOUNT_ROOT"),
+        no_translate: lookup("WSLGIT_NO_TRANSLATE")
+            .map(|list| parse_cmd_list(&list)),
+        force_translate: lookup("WSLGIT_FORCE_TRANSLATE")
+            .map(|flag| parse_flag(&flag)),
+        convert_relative_slashes: lookup("WSLGIT_CONVERT_RELATIVE_SLASHES")
+            .map(|flag| parse_flag(&flag)),
+        stdin: non_empty("WSLGIT_STDIN"),
+        wsl_exe: non_empty("WSLGIT_WSL_EXE"),
+        merge_stderr: lookup("WSLGIT_MERGE_STDERR")
+            .map(|flag| parse_flag(&flag)),
+        direct_cmds: lookup("WSLGIT_DIRECT_CMDS")
+            .map(|list| parse_cmd_list(&list)),
+        pager: non_empty("WSLGIT_PAGER"),
+        wsl_args: lookup("WSLGIT_WSL_ARGS").map(|args| parse_args(&args)),
+        output_eol: non_empty("WSLGIT_OUTPUT_EOL"),
+        start_retries: number_var(
+            "WSLGIT_START_RETRIES", non_empty("WSLGIT_START_RETRIES"), &mut errors),
+        output_separator: non_empty("WSLGIT_OUTPUT_SEPARATOR"),
+        literal_options: lookup("WSLGIT_LITERAL_OPTIONS")
+            .map(|list| parse_cmd_list(&list)),
+        translate: non_empty("WSLGIT_TRANSLATE"),
+        drives: non_empty("WSLGIT_DRIVES"),
+        max_capture: number_var(
+            "WSLGIT_MAX_CAPTURE", non_empty("WSLGIT_MAX_CAPTURE"), &mut errors),
+        fallback_git: non_empty("WSLGIT_FALLBACK_GIT"),
+        debug: lookup("WSLGIT_DEBUG").map(|flag| parse_flag(&flag)),
+        dry_run: lookup("WSLGIT_DRY_RUN").map(|flag| parse_flag(&flag)),
+    };
+    (config, errors)
+}
+
+impl Config {
+    // settings of self take precedence over those of other
+    fn merge(self, other: Config) -> Config {
+        Config {
+            distro: self.distro.or(other.distro),
+            user: self.user.or(other.user),
+            shell: self.shell.or(other.shell),
+            mount_root: self.mount_root.or(other.mount_root),
+            no_translate: self.no_translate.or(other.no_translate),
+            force_translate: self.force_translate.or(other.force_translate),
+            convert_relative_slashes: self.convert_relative_slashes
+                .or(other.convert_relative_slashes),
+            stdin: self.stdin.or(other.stdin),
+            wsl_exe: self.wsl_exe.or(other.wsl_exe),
+            merge_stderr: self.merge_stderr.or(other.merge_stderr),
+            direct_cmds: self.direct_cmds.or(other.direct_cmds),
+            pager: self.pager.or(other.pager),
+            wsl_args: self.wsl_args.or(other.wsl_args),
+            output_eol: self.output_eol.or(other.output_eol),
+            start_retries: self.start_retries.or(other.start_retries),
+            output_separator: self.output_separator.or(other.output_separator),
+            literal_options: self.literal_options.or(other.literal_options),
+            translate: self.translate.or(other.translate),
+            drives: self.drives.or(other.drives),
+            max_capture: self.max_capture.or(other.max_capture),
+            fallback_git: self.fallback_git.or(other.fallback_git),
+            debug: self.debug.or(other.debug),
+            dry_run: self.dry_run.or(other.dry_run),
+        }
+    }
+}
+
+enum Value {
+    Str(String),
+    Bool(bool),
+    Int(u64),
+    Array(Vec<String>),
+}
+
+// Parses the small subset of TOML needed for the configuration:
+// top level `key = value` pairs, where a value is a string, a boolean,
+// a non-negative integer or an array of strings on a single line, and
+// comments.
+fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+    for (index, line) in text.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let eq = line.find('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", line_no))?;
+        let key = line[..eq].trim();
+        let (value, rest) = parse_value(line[eq + 1..].trim_start())
+            .map_err(|err| format!("line {}: {}", line_no, err))?;
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(format!("line {}: unexpected `{}`", line_no, rest));
+        }
+        let type_error = || format!("line {}: invalid type for `{}`", line_no, key);
+        let number_error = || format!("line {}: `{}` must be a number", line_no, key);
+        match (key, value) {
+            ("distro", Value::Str(s)) => config.distro = Some(s),
+            ("user", Value::Str(s)) => config.user = Some(s),
+            ("shell", Value::Str(s)) => config.shell = Some(s),
+            ("mount_root", Value::Str(s)) => config.mount_root = Some(s),
+            ("stdin", Value::Str(s)) => config.stdin = Some(s),
+            ("output_eol", Value::Str(s)) => config.output_eol = Some(s),
+            ("start_retries", Value::Int(n)) =>
+                config.start_retries = Some(u32::try_from(n).map_err(|_| number_error())?),
+            ("start_retries", Value::Str(s)) =>
+                config.start_retries = Some(parse_number(&s).ok_or_else(number_error)?),
+            ("output_separator", Value::Str(s)) => config.output_separator = Some(s),
+            ("translate", Value::Str(s)) => config.translate = Some(s),
+            ("drives", Value::Str(s)) => config.drives = Some(s),
+            ("max_capture", Value::Int(n)) =>
+                config.max_capture = Some(usize::try_from(n).map_err(|_| number_error())?),
+            ("max_capture", Value::Str(s)) =>
+                config.max_capture = Some(parse_number(&s).ok_or_else(number_error)?),
+            ("fallback_git", Value::Str(s)) => config.fallback_git = Some(s),
+            ("wsl_exe", Value::Str(s)) => config.wsl_exe = Some(s),
+            ("pager", Value::Str(s)) => config.pager = Some(s),
+            ("no_translate", Value::Array(a)) => config.no_translate = Some(a),
+            ("no_translate", Value::Str(s)) =>
+                config.no_translate = Some(parse_cmd_list(&s)),
+            ("direct_cmds", Value::Array(a)) => config.direct_cmds = Some(a),
+            ("wsl_args", Value::Array(a)) => config.wsl_args = Some(a),
+            ("wsl_args", Value::Str(s)) => config.wsl_args = Some(parse_args(&s)),
+            ("direct_cmds", Value::Str(s)) =>
+                config.direct_cmds = Some(parse_cmd_list(&s)),
+            ("literal_options", Value::Array(a)) => config.literal_options = Some(a),
+            ("literal_options", Value::Str(s)) =>
+                config.literal_options = Some(parse_cmd_list(&s)),
+            ("force_translate", Value::Bool(b)) => config.force_translate = Some(b),
+            ("convert_relative_slashes", Value::Bool(b)) =>
+                config.convert_relative_slashes = Some(b),
+            ("merge_stderr", Value::Bool(b)) => config.merge_stderr = Some(b),
+            ("dry_run", Value::Bool(b)) => config.dry_run = Some(b),
+            ("debug", Value::Bool(b)) => config.debug = Some(b),
+            ("distro", _) | ("user", _) | ("shell", _) | ("mount_root", _)
+                | ("stdin", _) | ("wsl_exe", _) | ("no_translate", _) | ("force_translate", _)
+                | ("convert_relative_slashes", _) | ("merge_stderr", _)
+                | ("direct_cmds", _) | ("pager", _) | ("wsl_args", _)
+                | ("output_eol", _) | ("start_retries", _) | ("output_separator", _)
+                | ("literal_options", _) | ("translate", _) | ("drives", _)
+                | ("max_capture", _) | ("fallback_git", _) | ("debug", _)
+                | ("dry_run", _) =>
+                return Err(type_error()),
+            _ => return Err(format!("line {}: unknown key `{}`", line_no, key))
+        }
+    }
+    Ok(config)
+}
+
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    if text.starts_with('"') || text.starts_with('\'') {
+        let (s, rest) = parse_string(text)?;
+        return Ok((Value::Str(s), rest));
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_string(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected `,` or `]` in array".to_owned());
+            }
+        }
+    }
+    if let Some(rest) = text.strip_prefix("true") {
+        return Ok((Value::Bool(true), rest));
+    }
+    if let Some(rest) = text.strip_prefix("false") {
+        return Ok((Value::Bool(false), rest));
+    }
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        // TOML allows `_` between digits, like `1_048_576`
+        let end = text.find(|c: char| !c.is_ascii_digit() && c != '_').unwrap_or(text.len());
+        let digits = &text[..end];
+        if !digits.ends_with('_') && !digits.contains("__") {
+            if let Ok(number) = digits.replace('_', "").parse() {
+                return Ok((Value::Int(number), &text[end..]));
+            }
+        }
+    }
+    Err(format!("invalid value `{}`", text))
+}
+
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    // literal strings in single quotes are taken as they are, which is
+    // convenient for Windows paths; basic strings support simple escapes
+    if let Some(literal) = text.strip_prefix('\'') {
+        let end = literal.find('\'')
+            .ok_or_else(|| "unterminated string".to_owned())?;
+        return Ok((literal[..end].to_owned(), &literal[end + 1..]));
+    }
+    let basic = text.strip_prefix('"')
+        .ok_or_else(|| format!("expected string, found `{}`", text))?;
+    let mut s = String::new();
+    let mut chars = basic.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((s, &basic[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '\\')) => s.push('\\'),
+                Some((_, '"')) => s.push('"'),
+                Some((_, 'n')) => s.push('\n'),
+                Some((_, 't')) => s.push('\t'),
+                Some((_, e)) => return Err(format!("invalid escape `\\{}`", e)),
+                None => break
+            },
+            _ => s.push(c)
+        }
+    }
+    Err("unterminated string".to_owned())
+}
+
+
+#[test]
+fn no_translate_cmd_list() {
+    assert_eq!(parse_cmd_list("status, rev-parse"), &["status", "rev-parse"]);
+    assert_eq!(parse_cmd_list(" show ,,"), &["show"]);
+    assert!(parse_cmd_list("").is_empty());
+}
+
+#[test]
+fn config_file_parsing() {
+    let config = parse(r#"
+        # wslgit configuration
+        distro = "Debian"
+        user = 'me'   # comment
+        shell = "none"
+        mount_root = "/"
+        no_translate = ["remote", 'rev-parse']
+        force_translate = true
+        convert_relative_slashes = false
+        stdin = "lf"
+        wsl_exe = 'C:\Windows\System32\wsl.exe'
+        merge_stderr = true
+        direct_cmds = ["rev-parse"]
+        pager = "none"
+        wsl_args = ["--shell-type", "standard"]
+        output_eol = "crlf"
+        start_retries = 2
+        output_separator = "/"
+        literal_options = ["--author"]
+        translate = "out"
+        drives = "cd"
+        max_capture = 1_048_576
+        fallback_git = 'C:\Program Files\Git\cmd\git.exe'
+        debug = true
+        dry_run = false
+        "#).unwrap();
+    assert_eq!(config, Config {
+        distro: Some("Debian".to_owned()),
+        user: Some("me".to_owned()),
+        shell: Some("none".to_owned()),
+        mount_root: Some("/".to_owned()),
+        no_translate: Some(vec!["remote".to_owned(), "rev-parse".to_owned()]),
+        force_translate: Some(true),
+        convert_relative_slashes: Some(false),
+        stdin: Some("lf".to_owned()),
+        wsl_exe: Some("C:\\Windows\\System32\\wsl.exe".to_owned()),
+        merge_stderr: Some(true),
+        direct_cmds: Some(vec!["rev-parse".to_owned()]),
+        pager: Some("none".to_owned()),
+        wsl_args: Some(vec!["--shell-type".to_owned(), "standard".to_owned()]),
+        output_eol: Some("crlf".to_owned()),
+        start_retries: Some(2),
+        output_separator: Some("/".to_owned()),
+        literal_options: Some(vec!["--author".to_owned()]),
+        translate: Some("out".to_owned()),
+        drives: Some("cd".to_owned()),
+        max_capture: Some(1048576),
+        fallback_git: Some("C:\\Program Files\\Git\\cmd\\git.exe".to_owned()),
+        debug: Some(true),
+        dry_run: Some(false),
+    });
+    assert_eq!(parse("no_translate = \"remote, show\"").unwrap().no_translate,
+               Some(vec!["remote".to_owned(), "show".to_owned()]));
+    assert_eq!(parse("distro = \"a\\\"b\\\\c\"").unwrap().distro,
+               Some("a\"b\\c".to_owned()));
+    assert_eq!(parse("").unwrap(), Config::default());
+    // numbers may also be given as strings
+    assert_eq!(parse("start_retries = \"3\"").unwrap().start_retries, Some(3));
+}
+
+#[test]
+fn malformed_config_file() {
+    assert!(parse("distro").is_err());
+    assert!(parse("distro = Debian").is_err());
+    assert!(parse("distro = \"Debian").is_err());
+    assert!(parse("distro = \"Debian\" x").is_err());
+    assert!(parse("force_translate = \"yes\"").is_err());
+    assert!(parse("no_translate = [\"a\" \"b\"]").is_err());
+    assert!(parse("unknown = true").is_err());
+    assert_eq!(parse("start_retries = \"three\"").unwrap_err(),
+               "line 1: `start_retries` must be a number");
+    assert!(parse("start_retries = \"-1\"").is_err());
+    assert!(parse("start_retries = -1").is_err());
+    assert!(parse("start_retries = 1__0").is_err());
+    assert!(parse("start_retries = 99999999999").is_err());
+    assert!(parse("start_retries = true").is_err());
+    assert!(parse("max_capture = \"1M\"").is_err());
+}
+
+#[test]
+fn missing_or_malformed_config_file() {
+    let dir = env::temp_dir().join(format!("wslgit-config-{}", std::process::id()));
+    let path = dir.join(CONFIG_FILE_NAME);
+    assert!(load_file(&path).is_err());
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&path, "distro = Debian\n").unwrap();
+    let err = load_file(&path).unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn config_precedence() {
+    let file = parse(r#"
+        distro = "Debian"
+        user = "file-user"
+        force_translate = true
+        "#).unwrap();
+    let (env, errors) = from_env(|name| match name {
+        "WSLGIT_DISTRO" => Some("Ubuntu".to_owned()),
+        "WSLGIT_USER" => Some(String::new()),
+        "WSLGIT_FORCE_TRANSLATE" => Some("0".to_owned()),
+        _ => None
+    });
+    let config = env.merge(file);
+    // environment variables override the file
+    assert_eq!(config.distro, Some("Ubuntu".to_owned()));
+    assert_eq!(config.force_translate, Some(false));
+    // empty environment variables are ignored
+    assert_eq!(config.user, Some("file-user".to_owned()));
+    // settings in neither use the defaults
+    assert_eq!(config.shell, None);
+    assert_eq!(config.mount_root, None);
+    assert_eq!(config.no_translate, None);
+    assert_eq!(config.direct_cmds, None);
+    assert!(errors.is_empty());
+    assert_eq!(from_env(|name| match name {
+        "WSLGIT_WSL_ARGS" => Some(" --cd  /tmp ".to_owned()),
+        _ => None
+    }).0.wsl_args, Some(vec!["--cd".to_owned(), "/tmp".to_owned()]));
+    // environment variables which are not numbers are ignored
+    let numbers = |value: &str| from_env(|name| match name {
+        "WSLGIT_START_RETRIES" | "WSLGIT_MAX_CAPTURE" => Some(value.to_owned()),
+        _ => None
+    });
+    assert_eq!(numbers(" 3 "), (Config {
+        start_retries: Some(3), max_capture: Some(3), ..Config::default()
+    }, vec![]));
+    assert_eq!(numbers("three"), (Config::default(), vec![
+        "WSLGIT_START_RETRIES: `three` is not a number".to_owned(),
+        "WSLGIT_MAX_CAPTURE: `three` is not a number".to_owned()]));
+}
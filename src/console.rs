@@ -0,0 +1,174 @@
+// Programs in WSL cannot query the size of the Windows console, so
+// git would wrap e.g. `git log --graph` at the wrong width.
+// The size is passed to WSL in COLUMNS and LINES instead.
+const DEFAULT_TERM: &str = "xterm-256color";
+
+#[cfg(windows)]
+pub fn console_size() -> Option<(u16, u16)> {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Coord {
+        x: i16,
+        y: i16,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct SmallRect {
+        left: i16,
+        top: i16,
+        right: i16,
+        bottom: i16,
+    }
+
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct ConsoleScreenBufferInfo {
+        size: Coord,
+        cursor_position: Coord,
+        attributes: u16,
+        window: SmallRect,
+        maximum_window_size: Coord,
+    }
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleScreenBufferInfo(console_output: *mut c_void,
+                                      info: *mut ConsoleScreenBufferInfo) -> i32;
+    }
+
+    let mut info = std::mem::MaybeUninit::<ConsoleScreenBufferInfo>::uninit();
+    let window = unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE);
+        if handle.is_null() || handle as isize == -1 {
+            return None;
+        }
+        if GetConsoleScreenBufferInfo(handle, info.as_mut_ptr()) == 0 {
+            return None;
+        }
+        info.assume_init().window
+    };
+    let columns = i32::from(window.right) - i32::from(window.left) + 1;
+    let lines = i32::from(window.bottom) - i32::from(window.top) + 1;
+    if columns <= 0 || lines <= 0 {
+        return None;
+    }
+    Some((columns as u16, lines as u16))
+}
+
+#[cfg(not(windows))]
+pub fn console_size() -> Option<(u16, u16)> {
+    None
+}
+
+// Other programs may pipe text to wslgit in the code page of the console,
+// e.g. a commit message with umlauts, while git in WSL expects UTF-8.
+const CP_UTF8: u32 = 65001;
+
+#[cfg(windows)]
+pub fn input_codepage() -> Option<u32> {
+    extern "system" {
+        fn GetConsoleCP() -> u32;
+    }
+
+    // 0 without a console
+    match unsafe { GetConsoleCP() } {
+        0 | CP_UTF8 => None,
+        codepage => Some(codepage)
+    }
+}
+
+#[cfg(not(windows))]
+pub fn input_codepage() -> Option<u32> {
+    None
+}
+
+#[cfg(windows)]
+pub fn decode_codepage(bytes: &[u8], codepage: u32) -> Option<String> {
+    extern "system" {
+        fn MultiByteToWideChar(code_page: u32, flags: u32, multi_byte: *const u8,
+                               multi_byte_len: i32, wide: *mut u16, wide_len: i32) -> i32;
+    }
+
+    if bytes.is_empty() || codepage == CP_UTF8 {
+        return String::from_utf8(bytes.to_vec()).ok();
+    }
+    if bytes.len() > i32::MAX as usize {
+        return None;
+    }
+    let len = bytes.len() as i32;
+    let wide_len = unsafe {
+        MultiByteToWideChar(codepage, 0, bytes.as_ptr(), len, std::ptr::null_mut(), 0)
+    };
+    if wide_len <= 0 {
+        return None;
+    }
+    let mut wide = vec![0u16; wide_len as usize];
+    let written = unsafe {
+        MultiByteToWideChar(codepage, 0, bytes.as_ptr(), len, wide.as_mut_ptr(), wide_len)
+    };
+    if written <= 0 {
+        return None;
+    }
+    wide.truncate(written as usize);
+    String::from_utf16(&wide).ok()
+}
+
+#[cfg(not(windows))]
+pub fn decode_codepage(bytes: &[u8], codepage: u32) -> Option<String> {
+    // without the code pages of Windows, only UTF-8 is known
+    if codepage == CP_UTF8 {
+        String::from_utf8(bytes.to_vec()).ok()
+    } else {
+        None
+    }
+}
+
+pub fn terminal_env(console_size: Option<(u16, u16)>, term: Option<String>)
+        -> Vec<(String, String)> {
+    // without a console, e.g. when the output is redirected,
+    // git must not assume any terminal
+    let (columns, lines) = match console_size {
+        Some(size) => size,
+        None => return Vec::new()
+    };
+    let mut vars = vec![
+        ("COLUMNS".to_owned(), columns.to_string()),
+        ("LINES".to_owned(), lines.to_string())];
+    if term.map_or(true, |term| term.is_empty()) {
+        vars.push(("TERM".to_owned(), DEFAULT_TERM.to_owned()));
+    }
+    vars
+}
+
+
+#[test]
+fn terminal_env_for_console() {
+    let vars = |vars: &[(&str, &str)]| -> Vec<(String, String)> {
+        vars.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect()
+    };
+    assert_eq!(terminal_env(Some((120, 30)), None),
+               vars(&[("COLUMNS", "120"), ("LINES", "30"),
+                      ("TERM", "xterm-256color")]));
+    assert_eq!(terminal_env(Some((80, 25)), Some("vt100".to_owned())),
+               vars(&[("COLUMNS", "80"), ("LINES", "25")]));
+    assert!(terminal_env(None, None).is_empty());
+}
+
+#[cfg(windows)]
+#[test]
+fn oem_codepage_decoding() {
+    // code page 437 of the US console
+    assert_eq!(decode_codepage(b"Gr\x81\xe1e", 437), Some("Grüße".to_owned()));
+    assert_eq!(decode_codepage(b"plain", 850), Some("plain".to_owned()));
+}
+
+#[test]
+fn utf8_codepage_decoding() {
+    assert_eq!(decode_codepage("Grüße".as_bytes(), CP_UTF8), Some("Grüße".to_owned()));
+    assert_eq!(decode_codepage(b"Gr\x81\xe1e", CP_UTF8), None);
+}
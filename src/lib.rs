@@ -0,0 +1,2677 @@
+use std::io::{self, BufRead, Read, Write};
+use std::borrow::Cow;
+use std::path::{Path, Component, PrefixComponent, Prefix};
+use std::ffi::{OsStr, OsString};
+use std::env;
+use std::fs;
+use std::process::{Command, Stdio};
+
+#[macro_use] extern crate lazy_static;
+extern crate regex;
+use regex::bytes::{Regex, Captures, Replacer};
+
+pub mod cache;
+pub mod config;
+pub mod console;
+
+
+pub fn get_distro() -> Option<String> {
+    config::get().distro.clone()
+}
+
+fn get_drive_letter(pc: &PrefixComponent) -> Option<String> {
+    let drive_byte = match pc.kind() {
+        Prefix::VerbatimDisk(d) => Some(d),
+        Prefix::Disk(d) => Some(d),
+        _ => None
+    };
+    drive_byte.map(|drive_letter| char::from(drive_letter).to_ascii_lowercase().to_string())
+}
+
+fn normalize_mount_root(mount_root: &str) -> String {
+    // without trailing slash, so that the root directory `/` becomes empty
+    mount_root.trim_end_matches('/').to_owned()
+}
+
+fn parse_automount_root(wsl_conf: &str) -> Option<String> {
+    // the `root` key in the `[automount]` section
+    let mut in_automount = false;
+    for line in wsl_conf.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.starts_with('[') {
+            in_automount = line.eq_ignore_ascii_case("[automount]");
+        } else if in_automount {
+            let mut parts = line.splitn(2, '=');
+            if parts.next().map(str::trim) == Some("root") {
+                return parts.next().map(|root| root.trim().trim_matches('"').to_owned());
+            }
+        }
+    }
+    None
+}
+
+fn detect_mount_root() -> Option<String> {
+    let mut wsl = Command::new(config::get().wsl_exe.as_deref().unwrap_or("wsl"));
+    if let Some(distro) = get_distro() {
+        wsl.arg("-d").arg(distro);
+    }
+    let output = wsl.args(["--exec", "cat", "/etc/wsl.conf"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    // without /etc/wsl.conf, the default applies
+    Some(parse_automount_root(&String::from_utf8_lossy(&output.stdout))
+         .unwrap_or_else(|| "/mnt".to_owned()))
+}
+
+fn detect_mount_root_cached() -> Option<String> {
+    let path = match cache::cache_path() {
+        Some(path) => path,
+        None => return detect_mount_root()
+    };
+    cache::get_or_detect(
+        &path, &get_distro().unwrap_or_default(), "mount_root",
+        cache::env_hash(env::vars()), cache::now(), detect_mount_root)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WslVersion {
+    // Linux syscalls translated by Windows, with fast access to the
+    // Windows drives
+    Wsl1,
+    // a virtual machine, with the Windows drives mounted via 9p, which
+    // makes accessing them slower
+    Wsl2,
+}
+
+impl WslVersion {
+    pub fn number(self) -> u8 {
+        match self {
+            WslVersion::Wsl1 => 1,
+            WslVersion::Wsl2 => 2
+        }
+    }
+
+    fn from_number(number: &str) -> Option<WslVersion> {
+        match number {
+            "1" => Some(WslVersion::Wsl1),
+            "2" => Some(WslVersion::Wsl2),
+            _ => None
+        }
+    }
+}
+
+fn decode_wsl_output(output: &[u8]) -> String {
+    // wsl.exe writes its own messages in UTF-16
+    if output.len() % 2 == 0 && output.contains(&0) {
+        let units: Vec<u16> = output.chunks(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(output).into_owned()
+    }
+}
+
+fn parse_wsl_version(list: &str, distro: Option<&str>) -> Option<WslVersion> {
+    // the output of `wsl --list --verbose`, where `*` marks the default:
+    //   NAME      STATE           VERSION
+    // * Ubuntu    Running         2
+    //   Debian    Stopped         1
+    list.lines().skip(1)
+        .filter_map(|line| {
+            let line = line.trim();
+            let (is_default, line) = match line.strip_prefix('*') {
+                Some(line) => (true, line),
+                None => (false, line)
+            };
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            match columns.as_slice() {
+                [name, .., version] if columns.len() >= 3 =>
+                    Some((is_default, *name, *version)),
+                _ => None
+            }
+        })
+        .find(|&(is_default, name, _)| match distro {
+            Some(distro) => name.eq_ignore_ascii_case(distro),
+            None => is_default
+        })
+        .and_then(|(_, _, version)| WslVersion::from_number(version))
+}
+
+fn parse_default_distro(list: &str) -> Option<String> {
+    // the output of `wsl --list --verbose`, where `*` marks the default, or
+    // of `wsl --list`, where it is followed by `(Default)`:
+    //   Windows Subsystem for Linux Distributions:
+    //   Ubuntu (Default)
+    //   Debian
+    // Without any distribution, there is only a message instead.
+    list.lines().find_map(|line| {
+        let line = line.trim();
+        if let Some(line) = line.strip_prefix('*') {
+            return line.split_whitespace().next().map(str::to_owned);
+        }
+        line.strip_suffix("(Default)")
+            .map(str::trim_end)
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+    })
+}
+
+fn detect_default_distro() -> Option<String> {
+    let output = Command::new(config::get().wsl_exe.as_deref().unwrap_or("wsl"))
+        .args(["--list", "--verbose"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    parse_default_distro(&decode_wsl_output(&output.stdout))
+}
+
+pub fn default_distro() -> Option<String> {
+    // the distribution used without WSLGIT_DISTRO, e.g. to show it in
+    // diagnostics; None if there is none or wsl cannot be run
+    lazy_static! {
+        static ref DEFAULT_DISTRO: Option<String> = match cache::cache_path() {
+            Some(path) => cache::get_or_detect(
+                &path, "", "default_distro",
+                cache::env_hash(env::vars()), cache::now(), detect_default_distro),
+            None => detect_default_distro()
+        };
+    }
+    DEFAULT_DISTRO.clone()
+}
+
+fn detect_wsl_version() -> Option<String> {
+    let output = Command::new(config::get().wsl_exe.as_deref().unwrap_or("wsl"))
+        .args(["--list", "--verbose"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    parse_wsl_version(&decode_wsl_output(&output.stdout), get_distro().as_deref())
+        .map(|version| version.number().to_string())
+}
+
+pub fn get_wsl_version() -> Option<WslVersion> {
+    // detected once, e.g. to choose the commands run without a shell, which
+    // is slow to start with WSL 1, and cached like the mount root
+    lazy_static! {
+        static ref WSL_VERSION: Option<WslVersion> = match cache::cache_path() {
+            Some(path) => cache::get_or_detect(
+                &path, &get_distro().unwrap_or_default(), "wsl_version",
+                cache::env_hash(env::vars()), cache::now(), detect_wsl_version),
+            None => detect_wsl_version()
+        }.and_then(|version| WslVersion::from_number(&version));
+    }
+    *WSL_VERSION
+}
+
+pub fn get_mount_root() -> &'static str {
+    // Drives are mounted below the automount root configured in
+    // /etc/wsl.conf, which is /mnt by default. Reading it from WSL would
+    // require starting another process, so it is configured separately,
+    // or detected only with `auto`, and then cached.
+    lazy_static! {
+        static ref MOUNT_ROOT: String = normalize_mount_root(
+            &match config::get().mount_root.as_deref() {
+                Some("auto") => detect_mount_root_cached()
+                    .unwrap_or_else(|| "/mnt".to_owned()),
+                Some(root) => root.to_owned(),
+                None => "/mnt".to_owned()
+            });
+    }
+    &MOUNT_ROOT
+}
+
+fn parse_drives(drives: &str) -> Option<String> {
+    // drive letters like `cd`, in any case; without any letter, all drives
+    // are translated
+    let mut letters: Vec<char> = drives.chars()
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    letters.sort_unstable();
+    letters.dedup();
+    if letters.is_empty() {
+        None
+    } else {
+        Some(letters.into_iter().collect())
+    }
+}
+
+fn get_drives() -> Option<&'static str> {
+    // Only these drives are translated, e.g. not network drives, which are
+    // not mounted in WSL anyway
+    lazy_static! {
+        static ref DRIVES: Option<String> = config::get().drives.as_deref()
+            .and_then(parse_drives);
+    }
+    DRIVES.as_deref()
+}
+
+fn is_listed_drive(drives: Option<&str>, drive: u8) -> bool {
+    drives.map_or(true, |drives| drives.bytes().any(|d| d.eq_ignore_ascii_case(&drive)))
+}
+
+fn drive_class(drives: Option<&str>) -> String {
+    // the drive letters matched in paths like /mnt/c, in both cases
+    match drives {
+        Some(drives) => format!("[{}{}]", drives, drives.to_ascii_uppercase()),
+        None => "[A-Za-z]".to_owned()
+    }
+}
+
+fn path_drive(path: &Path) -> Option<u8> {
+    match path.components().next() {
+        Some(Component::Prefix(prefix_comp)) => match prefix_comp.kind() {
+            Prefix::Disk(d) | Prefix::VerbatimDisk(d) => Some(d),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+fn get_prefix_for_drive_at(mount_root: &str, drive: &str) -> String {
+    format!("{}/{}", mount_root, drive)
+}
+
+fn get_prefix_for_drive(drive: &str) -> String {
+    get_prefix_for_drive_at(get_mount_root(), drive)
+}
+
+fn is_target_distro(distro: &str) -> bool {
+    // without WSLGIT_DISTRO, git runs in the default distribution
+    is_same_distro(distro, get_distro().or_else(default_distro).as_deref())
+}
+
+fn is_same_distro(distro: &str, target: Option<&str>) -> bool {
+    // If the distribution cannot be determined, e.g. because wsl cannot
+    // be run, any distribution is accepted.
+    target.map_or(true, |target| target.eq_ignore_ascii_case(distro))
+}
+
+fn get_prefix_for_unc(server: &OsStr, share: &OsStr) -> Option<String> {
+    let server = server.to_str()?;
+    let share = share.to_str()?;
+    if (server.eq_ignore_ascii_case("wsl$")
+        || server.eq_ignore_ascii_case("wsl.localhost"))
+        && is_target_distro(share) {
+        // \\wsl$\<distro>\path is the native path /path inside WSL
+        return Some("/".to_owned());
+    }
+    Some(format!("//{}/{}", server, share))
+}
+
+fn get_prefix(pc: &PrefixComponent) -> Option<String> {
+    match pc.kind() {
+        Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) =>
+            get_prefix_for_unc(server, share),
+        _ => get_drive_letter(pc).map(|d| get_prefix_for_drive(&d))
+    }
+}
+
+pub fn translate_arg_to_unix(argument: OsString) -> OsString {
+    match argument.into_string() {
+        Ok(argument) => match translate_path_to_unix(&argument) {
+            Cow::Owned(translated) => OsString::from(translated),
+            Cow::Borrowed(_) => OsString::from(argument)
+        },
+        // arguments that are not valid unicode are passed unchanged
+        Err(argument) => argument
+    }
+}
+
+// options of git itself, which take their value as separate argument
+const GLOBAL_OPTIONS_WITH_VALUE: &[&str] = &[
+    "-C", "-c", "--git-dir", "--work-tree", "--namespace", "--config-env"];
+
+// arguments of git commands, which are followed by the path of a file
+// that may not exist yet; long options may also be joined with `=`
+const PATH_ARGS: &[(&str, &str)] = &[
+    ("archive", "-o"), ("archive", "--output"),
+    ("bundle", "create"), ("bundle", "verify"),
+    ("bundle", "list-heads"), ("bundle", "unbundle"),
+    ("config", "-f"), ("config", "--file"),
+    ("format-patch", "-o"), ("format-patch", "--output-directory"),
+    ("format-patch", "--output"), ("submodule", "--reference"),
+    ("apply", "--directory"), ("am", "--directory")];
+
+// subcommands of git commands with the number of their leading operands
+// which are paths that may not exist yet, e.g. the new worktree of
+// `git worktree add <path> [<commit>]`, or the repository and the path of
+// `git submodule add <repository> [<path>]`
+const PATH_OPERANDS: &[(&str, &str, usize)] = &[
+    ("worktree", "add", 1), ("worktree", "move", 2), ("worktree", "remove", 1),
+    ("worktree", "lock", 1), ("worktree", "unlock", 1), ("worktree", "repair", usize::MAX),
+    ("submodule", "add", 2)];
+
+// options of these subcommands, which take their value as separate argument
+const PATH_OPERAND_OPTIONS_WITH_VALUE: &[&str] = &[
+    "-b", "-B", "--reason", "--name", "--reference", "--depth"];
+
+#[derive(Default)]
+struct PathOperands {
+    // None until the subcommand is found
+    remaining: Option<usize>,
+    subcommand_seen: bool,
+    value_follows: bool,
+}
+
+impl PathOperands {
+    fn is_path(&mut self, command: &str, arg: &str) -> bool {
+        if self.value_follows {
+            self.value_follows = false;
+            return false;
+        }
+        if arg.starts_with('-') {
+            self.value_follows = self.remaining.is_some()
+                && PATH_OPERAND_OPTIONS_WITH_VALUE.contains(&arg);
+            return false;
+        }
+        if !self.subcommand_seen {
+            self.subcommand_seen = true;
+            self.remaining = PATH_OPERANDS.iter()
+                .find(|&&(cmd, subcommand, _)| cmd == command && subcommand == arg)
+                .map(|&(_, _, count)| count);
+            return false;
+        }
+        match self.remaining {
+            Some(count) if count > 0 => {
+                self.remaining = Some(count - 1);
+                true
+            }
+            _ => false
+        }
+    }
+}
+
+fn split_short_path_option<'a>(command: &str, arg: &'a str) -> Option<(&'a str, &'a str)> {
+    // a path joined with its short option, like `format-patch -oC:\patches`
+    PATH_ARGS.iter()
+        .find(|&&(cmd, path_arg)| cmd == command && path_arg.len() == 2
+            && path_arg.starts_with('-') && path_arg != "--"
+            && arg.len() > 2 && arg.starts_with(path_arg))
+        .map(|_| arg.split_at(2))
+}
+
+// options of git commands, which are followed by a message or a commit to
+// take the message from, which is never translated; `-F <file>` is a path
+const MESSAGE_ARGS: &[(&str, &str)] = &[
+    ("commit", "-m"), ("commit", "--message"),
+    ("commit", "-C"), ("commit", "--reuse-message"),
+    ("commit", "-c"), ("commit", "--reedit-message"),
+    ("commit-tree", "-m"), ("merge", "-m"), ("tag", "-m"), ("tag", "--message"),
+    ("notes", "-m"), ("notes", "--message"), ("stash", "-m"), ("stash", "--message")];
+
+fn is_message_arg(command: &str, arg: &str) -> bool {
+    // also short options combined with -m, like `commit -am <message>`
+    MESSAGE_ARGS.iter().any(|&(cmd, message_arg)| cmd == command && (arg == message_arg
+        || (message_arg == "-m" && arg.starts_with('-') && !arg.starts_with("--")
+            && arg.ends_with('m'))))
+}
+
+fn has_message_value(command: &str, arg: &str) -> bool {
+    // joined with its option, like `-mmessage` or `--message=message`
+    let argname = split_long_option(arg).0.strip_suffix('=');
+    MESSAGE_ARGS.iter().any(|&(cmd, message_arg)| cmd == command
+        && (argname == Some(message_arg)
+            || (message_arg == "-m" && arg.len() > 2 && arg.starts_with("-m"))))
+}
+
+// options of all git commands, whose value is a pattern or a format, and
+// never a path, e.g. `log --grep=C:\bug`; true if the value may also follow
+// as separate argument, like with `--grep <pattern>`
+const LITERAL_OPTIONS: &[(&str, bool)] = &[
+    ("--grep", true), ("--author", true), ("--committer", true),
+    ("--format", true), ("--pretty", false)];
+
+fn get_literal_options() -> &'static [String] {
+    config::get().literal_options.as_deref().unwrap_or(&[])
+}
+
+fn is_literal_option(arg: &str, extra_options: &[String]) -> bool {
+    // options added in WSLGIT_LITERAL_OPTIONS may be followed by their value
+    LITERAL_OPTIONS.iter().any(|&(option, separate)| separate && arg == option)
+        || extra_options.iter().any(|option| arg == option)
+}
+
+fn has_literal_value(arg: &str, extra_options: &[String]) -> bool {
+    let argname = split_long_option(arg).0.strip_suffix('=');
+    LITERAL_OPTIONS.iter().any(|&(option, _)| argname == Some(option))
+        || extra_options.iter().any(|option| argname == Some(option.as_str())
+            || (option.len() == 2 && !option.starts_with("--")
+                && arg.len() > 2 && arg.starts_with(option.as_str())))
+}
+
+pub fn expand_env_vars<F>(argument: &str, lookup: F) -> Cow<'_, str>
+        where F: Fn(&str) -> Option<String> {
+    // Windows references like %APPDATA% are expanded by cmd.exe, but not
+    // by bash, so they are expanded here. Unknown variables and other
+    // uses of `%`, like in `--format=%h %s`, are left as they are.
+    lazy_static! {
+        static ref ENV_VAR_RE: ::regex::Regex =
+            ::regex::Regex::new(r"%([A-Za-z_][A-Za-z0-9_()]*)%")
+            .expect("Failed to compile environment variable regex");
+    }
+    if !argument.contains('%') {
+        return Cow::Borrowed(argument);
+    }
+    ENV_VAR_RE.replace_all(argument, |caps: &::regex::Captures| {
+        lookup(&caps[1]).unwrap_or_else(|| caps[0].to_owned())
+    })
+}
+
+// only arguments known to be paths are expanded, while e.g. messages and
+// formats like `--format=%H%n` are passed on exactly
+fn expand_env_vars_arg(argument: OsString) -> OsString {
+    match argument.to_str().map(|arg| expand_env_vars(arg, |name| env::var(name).ok())) {
+        Some(Cow::Owned(expanded)) => OsString::from(expanded),
+        _ => argument
+    }
+}
+
+pub fn translate_args_to_unix(args: Vec<OsString>) -> Vec<OsString> {
+    // Options of git itself come before the git command. Only there,
+    // -C is followed by a directory, e.g. `git commit -C <commit>`
+    // reuses the message of a commit instead.
+    let mut translated = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let is_dir_option = arg == "-C";
+        let is_config_option = arg == "-c";
+        let takes_value = GLOBAL_OPTIONS_WITH_VALUE.iter().any(|&option| arg == option);
+        let is_option = arg.to_str().is_some_and(|arg| arg.starts_with('-'));
+        if !is_option {
+            let command = arg.to_string_lossy().into_owned();
+            translated.push(arg);
+            translate_command_args_to_unix(&command, args, &mut translated);
+            return translated;
+        }
+        translated.push(translate_arg_to_unix(arg));
+        if is_dir_option {
+            translated.extend(args.next().map(|arg| {
+                translate_quoted_arg_to_unix(
+                    expand_env_vars_arg(arg), true, translate_known_path_arg_to_unix)
+            }));
+        } else if is_config_option {
+            translated.extend(args.next().map(translate_config_arg_to_unix));
+        } else if takes_value {
+            translated.extend(args.next().map(|arg| {
+                translate_quoted_arg_to_unix(arg, false, translate_arg_to_unix)
+            }));
+        }
+    }
+    translated
+}
+
+fn translate_quoted_arg_to_unix<F>(argument: OsString, is_path: bool, translate: F) -> OsString
+        where F: Fn(OsString) -> OsString {
+    // A caller may enclose a path in quotes itself, e.g. `"C:\my repo"`.
+    // These quotes are removed from arguments known to be paths, and from
+    // others only if they are translated as a path without them, so that
+    // e.g. the message of `commit -m '"quoted"'` keeps its quotes, whether
+    // git is run by a shell or not.
+    let inner = match argument.to_str().and_then(strip_enclosing_quotes) {
+        Some(inner) => OsString::from(inner),
+        None => return translate(argument)
+    };
+    let translated = translate(inner.clone());
+    if is_path || translated != inner {
+        translated
+    } else {
+        argument
+    }
+}
+
+fn translate_config_arg_to_unix(argument: OsString) -> OsString {
+    match argument.into_string() {
+        Ok(argument) => match translate_config_to_unix(&argument) {
+            Cow::Owned(translated) => OsString::from(translated),
+            Cow::Borrowed(_) => OsString::from(argument)
+        },
+        Err(argument) => argument
+    }
+}
+
+pub fn translate_config_to_unix(config: &str) -> Cow<'_, str> {
+    // `key=value` of `git -c`, where the value may be a path, e.g.
+    // `core.hooksPath=C:\hooks`. Values may contain `=` themselves, so the
+    // key ends at the first one, and other values are kept exactly.
+    let key_len = match config.find('=') {
+        Some(pos) => pos + 1,
+        None => return Cow::Borrowed(config)
+    };
+    match (translate_include_condition(&config[..key_len - 1]),
+           translate_value_to_unix(config, key_len)) {
+        (Cow::Borrowed(_), translated) => translated,
+        (Cow::Owned(key), translated) =>
+            Cow::Owned(format!("{}={}", key, &translated[key_len..]))
+    }
+}
+
+fn translate_include_condition(key: &str) -> Cow<'_, str> {
+    // the directory in the condition of `includeIf.gitdir:C:/repo/.path`,
+    // where a trailing slash matches everything below the directory
+    const SECTION: &str = "includeif.";
+    let end = key.rfind('.').unwrap_or(0);
+    if !key.to_ascii_lowercase().starts_with(SECTION) || end <= SECTION.len() {
+        return Cow::Borrowed(key);
+    }
+    let condition = &key[SECTION.len()..end];
+    let dir = match ["gitdir:", "gitdir/i:"].iter()
+            .find_map(|prefix| condition.strip_prefix(prefix)) {
+        Some(dir) => dir,
+        None => return Cow::Borrowed(key)
+    };
+    match translate_path_to_unix(dir) {
+        Cow::Owned(mut translated) => {
+            if dir.ends_with(['/', '\\']) && !translated.ends_with('/') {
+                translated.push('/');
+            }
+            let start = end - dir.len();
+            Cow::Owned(format!("{}{}{}", &key[..start], translated, &key[end..]))
+        }
+        Cow::Borrowed(_) => Cow::Borrowed(key)
+    }
+}
+
+pub fn find_git_command_index(args: &[String]) -> Option<usize> {
+    // the first argument which is neither an option of git itself,
+    // nor the value of such an option
+    let mut args = args.iter().enumerate();
+    while let Some((index, arg)) = args.next() {
+        if !arg.starts_with('-') {
+            return Some(index);
+        }
+        if GLOBAL_OPTIONS_WITH_VALUE.contains(&arg.as_str()) {
+            args.next();
+        }
+    }
+    None
+}
+
+pub fn find_git_command(args: &[String]) -> Option<&str> {
+    find_git_command_index(args).map(|index| args[index].as_str())
+}
+
+fn translate_command_args_to_unix<I>(command: &str, args: I, translated: &mut Vec<OsString>)
+        where I: Iterator<Item = OsString> {
+    let literal_options = get_literal_options();
+    let mut path_operands = PathOperands::default();
+    let mut path_follows = false;
+    let mut literal_follows = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if literal_follows {
+            // a message or a pattern is no path, even if it mentions one
+            // or looks like `--`
+            translated.push(arg);
+            literal_follows = false;
+            continue;
+        }
+        if arg == "--" {
+            // everything after `--` is a path, even if it looks like an option
+            translated.push(arg);
+            translated.extend(args.map(|arg| {
+                translate_quoted_arg_to_unix(
+                    expand_env_vars_arg(arg), true, translate_pathspec_arg_to_unix)
+            }));
+            return;
+        }
+        let is_path_arg = PATH_ARGS.iter()
+            .any(|&(cmd, path_arg)| cmd == command && arg == path_arg);
+        let has_path_value = arg.to_str().is_some_and(|arg| {
+            let argname = split_long_option(arg).0.strip_suffix('=');
+            PATH_ARGS.iter()
+                .any(|&(cmd, path_arg)| cmd == command && argname == Some(path_arg))
+        });
+        let (is_literal_arg, has_literal_value) = match arg.to_str() {
+            Some(arg) => (
+                is_message_arg(command, arg) || is_literal_option(arg, literal_options),
+                has_message_value(command, arg) || has_literal_value(arg, literal_options)),
+            None => (false, false)
+        };
+        let is_path_operand = arg.to_str()
+            .is_some_and(|arg| path_operands.is_path(command, arg));
+        let short_path_value = arg.to_str()
+            .and_then(|arg| split_short_path_option(command, arg))
+            .map(|(option, path)| {
+                let path = expand_env_vars(path, |name| env::var(name).ok());
+                format!("{}{}", option, translate_known_path_to_unix(&path))
+            });
+        translated.push(if has_literal_value {
+            arg
+        } else if let Some(translated_arg) = short_path_value {
+            OsString::from(translated_arg)
+        } else if path_follows || has_path_value || is_path_operand {
+            translate_quoted_arg_to_unix(
+                expand_env_vars_arg(arg), true, translate_known_path_arg_to_unix)
+        } else {
+            translate_quoted_arg_to_unix(arg, false, translate_arg_to_unix)
+        });
+        path_follows = is_path_arg;
+        literal_follows = is_literal_arg;
+    }
+}
+
+fn translate_pathspec_arg_to_unix(argument: OsString) -> OsString {
+    let convert_slashes = config::get().convert_relative_slashes.unwrap_or(false);
+    match argument.into_string() {
+        Ok(argument) => OsString::from(
+            translate_pathspec_to_unix(&argument, convert_slashes).into_owned()),
+        Err(argument) => argument
+    }
+}
+
+fn translate_pathspec_to_unix(argument: &str, convert_slashes: bool) -> Cow<'_, str> {
+    // Without the guesswork needed for other arguments, relative paths are
+    // converted if enabled, whatever characters they contain. A leading
+    // `--name=` is part of the file name here, not an option.
+    match translate_value_to_unix(argument, 0) {
+        Cow::Borrowed(path) if convert_slashes && path.contains('\\') =>
+            Cow::Owned(path.replace('\\', "/")),
+        translated => translated
+    }
+}
+
+fn translate_known_path_arg_to_unix(argument: OsString) -> OsString {
+    match argument.into_string() {
+        Ok(argument) => OsString::from(translate_known_path_to_unix(&argument).into_owned()),
+        Err(argument) => argument
+    }
+}
+
+pub fn translate_known_path_to_unix(path: &str) -> Cow<'_, str> {
+    // The argument is known to be a path, e.g. a directory or an output
+    // file, so unlike other arguments, relative paths are converted even
+    // if they do not exist
+    match translate_path_to_unix(path) {
+        Cow::Borrowed(path) if path.contains('\\') => Cow::Owned(path.replace('\\', "/")),
+        translated => translated
+    }
+}
+
+pub fn translate_path_list_to_unix(list: &str) -> String {
+    // lists of paths are separated by `;` in Windows, but by `:` in WSL
+    list.split(';')
+        .filter(|path| !path.is_empty())
+        .map(|path| translate_path_to_unix(path).into_owned())
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn split_program(cmd: &str) -> (&str, &str) {
+    // split a command line into the program, without its quotes, and the
+    // rest; a quoted program extends to the matching quote, so that it may
+    // contain spaces, an unquoted one up to the first whitespace
+    match cmd.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => match cmd[1..].find(quote) {
+            Some(end) => (&cmd[1..end + 1], &cmd[end + 2..]),
+            None => (cmd, "")
+        },
+        _ => cmd.split_at(cmd.find(char::is_whitespace).unwrap_or(cmd.len()))
+    }
+}
+
+pub fn translate_command_to_unix(cmd: &str) -> Cow<'_, str> {
+    // The value is a command line for the shell, like
+    // `"C:\Program Files\Tool\t.exe" --wait`, of which only the program is
+    // translated if it is a Windows path. Its arguments are kept as they
+    // are, and programs like `vim` are left for WSL to find on PATH.
+    let (program, rest) = split_program(cmd);
+    if find_drive_paths(program).first() != Some(&0) && !program.starts_with("\\\\") {
+        return Cow::Borrowed(cmd);
+    }
+    match translate_path_to_unix(program) {
+        Cow::Owned(translated) => Cow::Owned(format!("{}{}", shell_escape(translated), rest)),
+        Cow::Borrowed(_) => Cow::Borrowed(cmd)
+    }
+}
+
+fn split_long_option(argument: &str) -> (&str, &str) {
+    // split `--key=value` after the first `=` into `--key=` and `value`,
+    // all other arguments have no option name
+    if argument.starts_with("--") {
+        if let Some(pos) = argument.find('=') {
+            return argument.split_at(pos + 1);
+        }
+    }
+    ("", argument)
+}
+
+fn split_pathspec_magic(argument: &str) -> (&str, &str) {
+    // split the magic of a pathspec, like `:(exclude)` in
+    // `:(exclude)C:\build` or `:!` in `:!C:\build`, from the path
+    if !argument.starts_with(':') {
+        return ("", argument);
+    }
+    if argument.starts_with(":(") {
+        return match argument.find(')') {
+            Some(pos) => argument.split_at(pos + 1),
+            None => ("", argument)
+        };
+    }
+    // the short form uses the magic signatures `/`, `!` and `^`,
+    // optionally terminated by another colon
+    let mut end = 1 + argument[1..]
+        .find(|c| c != '/' && c != '!' && c != '^')
+        .unwrap_or(argument.len() - 1);
+    if argument[end..].starts_with(':') {
+        end += 1;
+    }
+    argument.split_at(end)
+}
+
+fn normalize_separators(path: &str) -> String {
+    // Use backslashes only and collapse repeated separators, e.g.
+    // C:/foo//bar\\baz becomes C:\foo\bar\baz, but keep the leading
+    // double backslash of UNC paths. Verbatim paths (\\?\) are left alone,
+    // as forward slashes are not separators in them.
+    if path.starts_with("\\\\?\\") {
+        return path.to_owned();
+    }
+    let (unc, rest) = if path.starts_with("\\\\") || path.starts_with("//") {
+        ("\\\\", &path[2..])
+    } else {
+        ("", path)
+    };
+    let mut normalized = String::from(unc);
+    for c in rest.chars() {
+        let c = if c == '/' { '\\' } else { c };
+        if c != '\\' || !normalized.ends_with('\\') {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+fn strip_verbatim_prefix(path: &str) -> Cow<'_, str> {
+    // Extended-length paths, like \\?\C:\x or \\?\UNC\server\share\x, are
+    // translated like the same paths without the prefix. Others, e.g. of
+    // volumes like \\?\Volume{<guid>}\, or the prefix alone, are kept.
+    let rest = match path.strip_prefix("\\\\?\\") {
+        Some(rest) => rest,
+        None => return Cow::Borrowed(path)
+    };
+    match rest.as_bytes() {
+        [b'U' | b'u', b'N' | b'n', b'C' | b'c', b'\\', ..] =>
+            Cow::Owned(format!("\\\\{}", &rest[4..])),
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => Cow::Borrowed(rest),
+        _ => Cow::Borrowed(path)
+    }
+}
+
+fn is_drive_relative(path: &Path) -> bool {
+    match path.components().next() {
+        Some(Component::Prefix(prefix_comp)) => match prefix_comp.kind() {
+            Prefix::Disk(_) => !path.has_root(),
+            _ => false
+        },
+        _ => false
+    }
+}
+
+fn convert_relative_path(path: &str) -> Option<String> {
+    // Relative paths like src\main.rs, which do not exist, e.g. when they
+    // are removed already, cannot be told apart from other arguments
+    // with backslashes for sure. They are converted only if
+    //  - the argument contains a backslash, but no colon, like drive letters,
+    //    and does neither start nor end with a slash or a backslash,
+    //  - there are no repeated backslashes, as in escape sequences, and
+    //  - there is no whitespace, no quote and no other character which is
+    //    invalid in Windows paths or used in formats, like %, which are a
+    //    strong sign of some other text, e.g. a commit message.
+    let is_relative_path = path.contains('\\')
+        && !path.contains(':')
+        && !path.contains("\\\\")
+        && !path.starts_with(['\\', '/', '-'])
+        && !path.ends_with(['\\', '/'])
+        && !path.chars().any(|c| c.is_whitespace() || "\"'`$%*?<>|".contains(c));
+    if is_relative_path {
+        Some(path.replace('\\', "/"))
+    } else {
+        None
+    }
+}
+
+fn is_win_path(path: &Path) -> bool {
+    // Paths with a root, but without drive or server, like /home/me, are
+    // paths in WSL, e.g. translated before. They are never translated, so
+    // that translating an argument again does not change it anymore.
+    let has_prefix = matches!(path.components().next(), Some(Component::Prefix(_)));
+    if path.has_root() && !has_prefix {
+        return false;
+    }
+    // relative paths are indistinguishable from other strings,
+    // unless they point to something that exists
+    path.is_absolute() || path.exists()
+}
+
+fn is_url(arg: &str) -> bool {
+    // `scheme://`, where the scheme may be a single letter, so that e.g.
+    // a://host is not taken as the drive path a:\host
+    match arg.find("://") {
+        Some(pos) if pos > 0 => {
+            let scheme = &arg[..pos];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        }
+        _ => false
+    }
+}
+
+fn find_drive_paths(text: &str) -> Vec<usize> {
+    // start positions of drive paths like C:\ or C:/ at the start of the
+    // text or after whitespace, but neither within a word nor URLs like
+    // a://host
+    let bytes = text.as_bytes();
+    (0..bytes.len())
+        .filter(|&i| {
+            (i == 0 || bytes[i - 1].is_ascii_whitespace())
+                && bytes[i].is_ascii_alphabetic()
+                && bytes.get(i + 1) == Some(&b':')
+                && match bytes.get(i + 2) {
+                    Some(b'\\') => true,
+                    Some(b'/') => bytes.get(i + 3) != Some(&b'/'),
+                    _ => false
+                }
+        })
+        .collect()
+}
+
+fn translate_embedded_paths(text: &str, starts: &[usize]) -> String {
+    // Each path extends up to the whitespace before the next path, so that
+    // paths with spaces, like C:\Program Files\diff.exe, are kept together.
+    // The text before the first path is kept unchanged.
+    let mut translated = String::from(&text[..starts[0]]);
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(text.len(), |&next| next);
+        let segment = &text[start..end];
+        let path = segment.trim_end();
+        translated.push_str(&translate_path_to_unix(path));
+        translated.push_str(&segment[path.len()..]);
+    }
+    translated
+}
+
+fn translate_file_url_to_unix(url: &str) -> Option<String> {
+    // file:///C:/path or file://C:/path
+    let path = url.strip_prefix("file://")?;
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let bytes = path.as_bytes();
+    if bytes.len() < 2 || !bytes[0].is_ascii_alphabetic() || bytes[1] != b':'
+            || !is_listed_drive(get_drives(), bytes[0]) {
+        return None;
+    }
+    let rest = &path[2..];
+    if !rest.is_empty() && !rest.starts_with('/') && !rest.starts_with('\\') {
+        return None;
+    }
+    let drive = path[..1].to_lowercase();
+    Some(format!("file://{}{}",
+                 get_prefix_for_drive(&drive),
+                 rest.replace('\\', "/")))
+}
+
+fn may_contain_win_path(argument: &str) -> bool {
+    // Drive paths and URLs need a colon, UNC and relative paths a
+    // backslash or a leading `//`. Any other argument, like most paths
+    // passed to `git add`, is returned right away, without looking for
+    // paths in it or checking whether it exists.
+    argument.bytes().any(|c| c == b':' || c == b'\\') || argument.starts_with("//")
+}
+
+pub fn translate_path_to_unix(argument: &str) -> Cow<'_, str> {
+    if !may_contain_win_path(argument) {
+        return Cow::Borrowed(argument);
+    }
+    translate_value_to_unix(argument, split_long_option(argument).0.len())
+}
+
+fn translate_value_to_unix(argument: &str, argname_len: usize) -> Cow<'_, str> {
+    // arguments that are not translated are borrowed, not copied;
+    // the value starts after the option name, e.g. `--file=`
+    let (argname, arg) = argument.split_at(argname_len);
+    let (magic, arg) = split_pathspec_magic(arg);
+    let prefix = &argument[..argname.len() + magic.len()];
+    let arg = &*strip_verbatim_prefix(arg);
+    if let Some(url) = translate_file_url_to_unix(arg) {
+        return Cow::Owned(format!("{}{}", &prefix, &url));
+    }
+    if is_url(arg) {
+        return Cow::Borrowed(argument);
+    }
+    let drive_paths = find_drive_paths(arg);
+    if drive_paths.len() > 1 || drive_paths.first().is_some_and(|&start| start > 0) {
+        // e.g. a command with paths as arguments
+        return Cow::Owned(format!(
+            "{}{}", prefix, translate_embedded_paths(arg, &drive_paths)));
+    }
+    let normalized = normalize_separators(arg);
+    let mut win_path = Path::new(&normalized);
+    let resolved;
+    if is_drive_relative(win_path) {
+        // A drive relative path like C:foo is relative to the current
+        // directory of drive C, which is only known to Windows. Resolve
+        // it to an absolute path, but like other relative paths, only
+        // if it exists, as e.g. `a:file` may as well be a revision
+        // and a path in git. The result is an extended-length path, like
+        // \\?\C:\dir\foo, which is translated like C:\dir\foo.
+        resolved = match fs::canonicalize(win_path) {
+            Ok(abs_path) => abs_path,
+            Err(_) => return Cow::Borrowed(argument)
+        };
+        win_path = &resolved;
+    }
+    if !path_drive(win_path).map_or(true, |drive| is_listed_drive(get_drives(), drive)) {
+        return Cow::Borrowed(argument);
+    }
+    if is_win_path(win_path) {
+        let mut wsl_path = String::from(prefix);
+        for c in win_path.components() {
+            match c {
+                Component::Prefix(prefix_comp) => match get_prefix(&prefix_comp) {
+                    Some(p) => wsl_path.push_str(&p),
+                    // e.g. a volume GUID path like \\?\Volume{...}\dir, or a
+                    // device like \\.\COM1, which have no path inside WSL
+                    None => return Cow::Borrowed(argument)
+                },
+                Component::RootDir => {},
+                _ => {
+                    let d = match c.as_os_str().to_str() {
+                        Some(d) => d,
+                        None => return Cow::Borrowed(argument)
+                    };
+                    if wsl_path.len() > prefix.len() && !wsl_path.ends_with('/') {
+                        wsl_path.push('/');
+                    }
+                    wsl_path.push_str(d);
+                }
+            }
+        }
+        return Cow::Owned(wsl_path);
+    }
+    if config::get().convert_relative_slashes.unwrap_or(false) {
+        if let Some(path) = convert_relative_path(arg) {
+            return Cow::Owned(format!("{}{}", prefix, path));
+        }
+    }
+    Cow::Borrowed(argument)
+}
+
+// Characters which end an unquoted path in the output: whitespace, NUL,
+// quotes and `:`, which cannot be part of a Windows path, and the escape
+// character, which starts e.g. the color reset after a path. This leaves e.g.
+// the line and column of `git grep -n` output, `/mnt/c/a.rs:10:5:match`, alone.
+const PATH_END_CHARS: &str = r#"\s\x00'":\x1b"#;
+
+// the escape sequence which sets the color, e.g. with `color.ui=always`
+const COLOR_SEQUENCE: &str = r"\x1b\[[0-9;]*m";
+
+fn wslpath_regex(mount_root: &str, drives: Option<&str>) -> Regex {
+    // A path starts at the beginning of a line or after a character that
+    // cannot be part of a path, like whitespace, quotes or `:`, so that
+    // e.g. /home/mnt/c/ is left alone. The path ends before any of
+    // PATH_END_CHARS, so that multiple paths in one line are found, but
+    // continues after a space with a word that contains a slash without
+    // starting with one, like `/mnt/c/my repo/.git`, so that all of its
+    // separators are replaced. A word starting with `(` is no part of it,
+    // like the description in `/mnt/c/sub (heads/main)` of `submodule status`.
+    // A path may also start right after a color, e.g. `\x1b[31m/mnt/c/a`.
+    // After a tab, the path is the last field of the line, e.g. of
+    // `git ls-tree`, `100644 blob <sha>\t/mnt/c/my dir/a b`, and extends to
+    // its end. These paths use their own groups, prefixed with `t`.
+    // Paths with special characters are quoted by git, e.g. "/mnt/c/a\tb".
+    // Such a path ends at the closing quote only, including any whitespace
+    // or escape sequences, so that nothing inside it is taken as another
+    // path. The quoted paths use their own groups, prefixed with `q`.
+    let root = regex::escape(mount_root);
+    Regex::new(&format!(
+        r#"(?m-u)(?P<qpre>")(?P<qurl>file://)?{root}/(?P<qdrive>{drive})(?P<qpath>/(?:[^"\\\n]|\\.)*")|(?P<tpre>\t)(?P<turl>file://)?{root}/(?P<tdrive>{drive})(?P<tpath>/[^\n\r\x00\x1b]*)|(?P<pre>^|[^\w/.~-]|{color})(?P<url>file://)?{root}/(?P<drive>{drive})(?P<path>/[^{end}]*(?: [^{end}/(][^{end}/]*/[^{end}]*)*)"#,
+        root = root, drive = drive_class(drives), end = PATH_END_CHARS, color = COLOR_SEQUENCE))
+        .expect("Failed to compile WSLPATH regex")
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PathSeparator {
+    // native Windows paths, like C:\a\b
+    Backslash,
+    // with forward slashes, like C:/a/b
+    Slash,
+}
+
+impl PathSeparator {
+    pub fn parse(separator: &str) -> Option<PathSeparator> {
+        match separator {
+            "\\" => Some(PathSeparator::Backslash),
+            "/" => Some(PathSeparator::Slash),
+            _ => None
+        }
+    }
+}
+
+pub fn get_path_separator() -> PathSeparator {
+    lazy_static! {
+        static ref PATH_SEPARATOR: PathSeparator = config::get().output_separator.as_ref()
+            .and_then(|separator| PathSeparator::parse(separator))
+            .unwrap_or(PathSeparator::Backslash);
+    }
+    *PATH_SEPARATOR
+}
+
+pub fn translate_path_to_win(line: &[u8]) -> Cow<'_, [u8]> {
+    lazy_static! {
+        static ref WSLPATH_RE: Regex = wslpath_regex(get_mount_root(), get_drives());
+    }
+    translate_path_to_win_with(&WSLPATH_RE, line, get_path_separator())
+}
+
+fn translate_path_to_win_with<'a>(wslpath_re: &Regex, line: &'a [u8],
+                                  separator: PathSeparator) -> Cow<'a, [u8]> {
+    wslpath_re.replace_all(line, WinPathReplacer { separator })
+}
+
+fn push_win_path(dst: &mut Vec<u8>, drive: &[u8], path: &[u8], separator: PathSeparator,
+                 quoted: bool) {
+    // The drive letter is in upper case, as Windows shows it, so that a
+    // path passed in comes out exactly the same again. A backslash within
+    // a path quoted by git must be escaped itself.
+    dst.extend(drive.iter().map(u8::to_ascii_uppercase));
+    dst.push(b':');
+    if separator == PathSeparator::Slash {
+        dst.extend_from_slice(path);
+        return;
+    }
+    let backslash: &[u8] = if quoted { b"\\\\" } else { b"\\" };
+    for &c in path {
+        if c == b'/' {
+            dst.extend_from_slice(backslash);
+        } else {
+            dst.push(c);
+        }
+    }
+}
+
+struct WinPathReplacer {
+    separator: PathSeparator,
+}
+
+impl Replacer for WinPathReplacer {
+    // appends to the output directly, without allocating for each match
+    fn replace_append(&mut self, caps: &Captures, dst: &mut Vec<u8>) {
+        let quoted = caps.name("qdrive").is_some();
+        let (pre, url, drive, path) = if quoted {
+            ("qpre", "qurl", "qdrive", "qpath")
+        } else if caps.name("tdrive").is_some() {
+            ("tpre", "turl", "tdrive", "tpath")
+        } else {
+            ("pre", "url", "drive", "path")
+        };
+        dst.extend_from_slice(&caps[pre]);
+        // file URLs need an additional slash before the drive letter,
+        // e.g. file:///mnt/c/path becomes file:///C:/path, and keep
+        // their slashes
+        if caps.name(url).is_some() {
+            dst.extend_from_slice(b"file:///");
+            push_win_path(dst, &caps[drive], &caps[path], PathSeparator::Slash, quoted);
+        } else {
+            push_win_path(dst, &caps[drive], &caps[path], self.separator, quoted);
+        }
+    }
+}
+
+fn leading_wslpath_regex(mount_root: &str, drives: Option<&str>) -> Regex {
+    // The file name ends with `:`, or with NUL with -z. Context lines use
+    // `-` instead, like `/mnt/c/a.rs-2-text`, which is only recognized
+    // together with the line number. With colors, the file name follows
+    // its color and ends before the reset, like `\x1b[35m/mnt/c/a.rs\x1b[m`.
+    Regex::new(&format!(
+        r"(?-u)\A(?P<color>(?:{})*){}/(?P<drive>{})(?P<path>/[^:\x00\n\x1b]*?)(?:[:\x00\n\x1b]|-[0-9]+-|\z)",
+        COLOR_SEQUENCE, regex::escape(mount_root), drive_class(drives)))
+        .expect("Failed to compile leading WSLPATH regex")
+}
+
+pub fn translate_leading_path_to_win(line: &[u8]) -> Cow<'_, [u8]> {
+    // Only a path at the start of the line is translated, e.g. the file
+    // name in `git grep` output like `/mnt/c/a.rs:10:5:text`, while any path
+    // in the matched text is part of the file content and left alone.
+    lazy_static! {
+        static ref LEADING_WSLPATH_RE: Regex =
+            leading_wslpath_regex(get_mount_root(), get_drives());
+    }
+    translate_leading_path_to_win_with(&LEADING_WSLPATH_RE, line, get_path_separator())
+}
+
+fn translate_leading_path_to_win_with<'a>(leading_wslpath_re: &Regex, line: &'a [u8],
+                                          separator: PathSeparator) -> Cow<'a, [u8]> {
+    let caps = match leading_wslpath_re.captures(line) {
+        Some(caps) => caps,
+        None => return Cow::Borrowed(line)
+    };
+    let path = caps.name("path").expect("Leading WSLPATH regex without path");
+    let mut translated = Vec::with_capacity(line.len());
+    translated.extend_from_slice(&caps["color"]);
+    push_win_path(&mut translated, &caps["drive"], path.as_bytes(), separator, false);
+    translated.extend_from_slice(&line[path.end()..]);
+    Cow::Owned(translated)
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LineEnding {
+    // as written by git
+    Keep,
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn parse(eol: &str) -> Option<LineEnding> {
+        match eol {
+            "keep" => Some(LineEnding::Keep),
+            "lf" => Some(LineEnding::Lf),
+            "crlf" => Some(LineEnding::Crlf),
+            _ => None
+        }
+    }
+}
+
+fn convert_line_ending(line: &[u8], eol: LineEnding) -> Cow<'_, [u8]> {
+    let content = match line.strip_suffix(b"\n") {
+        Some(content) => content.strip_suffix(b"\r").unwrap_or(content),
+        // the last line without line ending
+        None => return Cow::Borrowed(line)
+    };
+    let ending: &[u8] = match eol {
+        LineEnding::Keep => return Cow::Borrowed(line),
+        LineEnding::Lf => b"\n",
+        LineEnding::Crlf => b"\r\n"
+    };
+    if &line[content.len()..] == ending {
+        return Cow::Borrowed(line);
+    }
+    let mut converted = Vec::with_capacity(content.len() + ending.len());
+    converted.extend_from_slice(content);
+    converted.extend_from_slice(ending);
+    Cow::Owned(converted)
+}
+
+pub fn translate_grep_lines<R: BufRead, W: Write>(
+    input: R, out: &mut W, eol: LineEnding) -> io::Result<()> {
+    translate_grep_lines_capped(input, out, eol, usize::MAX).map(|_| ())
+}
+
+pub fn translate_grep_lines_capped<R: BufRead, W: Write>(
+    input: R, out: &mut W, eol: LineEnding, max_capture: usize) -> io::Result<bool> {
+    // With -z, git grep ends the file name with NUL instead of `:`, but
+    // every match still ends with a newline.
+    translate_capped(input, out, b'\n', max_capture, |line, out: &mut W| {
+        out.write_all(&convert_line_ending(&translate_leading_path_to_win(line), eol))
+    })
+}
+
+fn translate_capped<R: BufRead, W: Write, F>(
+    mut input: R, out: &mut W, separator: u8, max_capture: usize, translate: F)
+        -> io::Result<bool>
+        where F: Fn(&[u8], &mut W) -> io::Result<()> {
+    // A record is held in memory until its separator arrives. One which
+    // is longer than max_capture, e.g. a huge line without any newline,
+    // is passed on unchanged instead, together with all of the rest of
+    // the output. Returns whether this happened.
+    let limit = (max_capture as u64).saturating_add(1);
+    let mut record = Vec::new();
+    while (&mut input).take(limit).read_until(separator, &mut record)? > 0 {
+        if record.len() > max_capture {
+            out.write_all(&record)?;
+            io::copy(&mut input, out)?;
+            out.flush()?;
+            return Ok(true);
+        }
+        translate(&record, out)?;
+        record.clear();
+    }
+    out.flush()?;
+    Ok(false)
+}
+
+pub fn translate_lines<R: BufRead, W: Write>(mut input: R, out: &mut W) -> io::Result<()> {
+    // Progress meters, e.g. of git clone, update their line by ending it
+    // with \r instead of \n. Both end a line here, and every line is
+    // written as soon as it is complete, so that the progress stays live.
+    let mut line = Vec::new();
+    loop {
+        let (line_complete, used) = {
+            let available = input.fill_buf()?;
+            if available.is_empty() {
+                break;
+            }
+            match available.iter().position(|&b| b == b'\n' || b == b'\r') {
+                Some(pos) => {
+                    line.extend_from_slice(&available[..=pos]);
+                    (true, pos + 1)
+                }
+                None => {
+                    line.extend_from_slice(available);
+                    (false, available.len())
+                }
+            }
+        };
+        input.consume(used);
+        if line_complete {
+            out.write_all(&translate_path_to_win(&line))?;
+            out.flush()?;
+            line.clear();
+        }
+    }
+    if !line.is_empty() {
+        out.write_all(&translate_path_to_win(&line))?;
+    }
+    out.flush()
+}
+
+// like git, only the start of the output is checked for binary data
+const BINARY_CHECK_LEN: usize = 8000;
+
+fn looks_binary(start: &[u8], separator: u8) -> bool {
+    // Text does not contain NUL bytes, unless they separate the records
+    // with -z. Otherwise, many control characters are a sign of binary data.
+    let start = &start[..start.len().min(BINARY_CHECK_LEN)];
+    if separator != b'\0' && start.contains(&b'\0') {
+        return true;
+    }
+    let control_chars = start.iter()
+        .filter(|&&b| b < 0x20 && b != separator
+                && !b"\t\n\r\x08\x0c\x1b".contains(&b))
+        .count();
+    control_chars * 10 > start.len()
+}
+
+pub fn translate_records<R: BufRead, W: Write>(
+    input: R, out: &mut W, separator: u8, eol: LineEnding) -> io::Result<()> {
+    translate_records_capped(input, out, separator, eol, usize::MAX).map(|_| ())
+}
+
+pub fn translate_records_capped<R: BufRead, W: Write>(
+    mut input: R, out: &mut W, separator: u8, eol: LineEnding, max_capture: usize)
+        -> io::Result<bool> {
+    // binary output, e.g. of a blob, is passed on unchanged
+    if looks_binary(input.fill_buf()?, separator) {
+        io::copy(&mut input, out)?;
+        out.flush()?;
+        return Ok(false);
+    }
+    // translate record by record as the output arrives, instead of waiting
+    // for the process to finish and keeping all of its output in memory
+    // records separated by NUL with -z are no lines
+    let eol = if separator == b'\n' { eol } else { LineEnding::Keep };
+    translate_capped(input, out, separator, max_capture, |record, out: &mut W| {
+        out.write_all(&convert_line_ending(&translate_path_to_win(record), eol))
+    })
+}
+
+pub fn transcode_line<F>(line: &[u8], decode: F) -> Cow<'_, [u8]>
+        where F: Fn(&[u8]) -> Option<String> {
+    // Lines which are valid UTF-8 already, like everything read from the
+    // console itself, are kept. Others are decoded from the code page of
+    // the console, if possible, and kept as they are otherwise.
+    if std::str::from_utf8(line).is_ok() {
+        return Cow::Borrowed(line);
+    }
+    match decode(line) {
+        Some(decoded) => Cow::Owned(decoded.into_bytes()),
+        None => Cow::Borrowed(line)
+    }
+}
+
+pub fn copy_input<R: BufRead, W: Write, F>(
+    mut input: R, out: &mut W, crlf_to_lf: bool, decode: F) -> io::Result<()>
+        where F: Fn(&[u8]) -> Option<String> {
+    // copy line by line, so that git gets each line as soon as it is
+    // entered, e.g. when a commit message is typed into the console
+    let mut line = Vec::new();
+    while input.read_until(b'\n', &mut line)? > 0 {
+        if crlf_to_lf && line.ends_with(b"\r\n") {
+            line.truncate(line.len() - 2);
+            line.push(b'\n');
+        }
+        out.write_all(&transcode_line(&line, &decode))?;
+        out.flush()?;
+        line.clear();
+    }
+    Ok(())
+}
+
+// printed by the shell right before git is started
+pub const OUTPUT_MARKER: &str = "__WSLGIT_OUTPUT_START__";
+
+pub fn skip_to_marker<R: BufRead>(input: &mut R, marker: &str) -> io::Result<Vec<u8>> {
+    // Discard all lines up to and including the marker line. If there is
+    // no marker line, everything is read and returned instead.
+    let mut skipped = Vec::new();
+    let mut line = Vec::new();
+    while input.read_until(b'\n', &mut line)? > 0 {
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        if content == marker.as_bytes() {
+            return Ok(Vec::new());
+        }
+        skipped.extend_from_slice(&line);
+        line.clear();
+    }
+    Ok(skipped)
+}
+
+fn strip_enclosing_quotes(arg: &str) -> Option<&str> {
+    // Only an argument completely enclosed in one pair of matching quotes
+    // counts as already quoted. Any other quote of the same kind inside, or
+    // anything a shell would still expand inside double quotes, makes it
+    // ambiguous, and such arguments are passed on literally instead.
+    // Backslashes are separators of Windows paths here, not escapes.
+    let quote = arg.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let inner = arg.strip_prefix(quote)?.strip_suffix(quote)?;
+    let special: &[char] = if quote == '"' { &['"', '$', '`'] } else { &['\''] };
+    if inner.contains(special) {
+        return None;
+    }
+    Some(inner)
+}
+
+pub fn shell_escape(arg: String) -> String {
+    // Arguments consisting only of these characters are never interpreted
+    // by the shell and are passed unquoted to keep the command readable.
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_./:=-".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg;
+    }
+    // Everything else is single quoted, as nothing inside single quotes is
+    // special to the shell. A single quote itself cannot be escaped inside
+    // single quotes, so the quoting is closed, an escaped quote is added,
+    // and the quoting is reopened.
+    format!("'{}'", arg.replace("'", "'\\''"))
+}
+
+
+#[cfg(test)]
+fn os_args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+#[cfg(test)]
+fn string_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|&arg| arg.to_owned()).collect()
+}
+
+#[test]
+fn automount_root_parsing() {
+    assert_eq!(parse_automount_root("[automount]\nroot = /\n"), Some("/".to_owned()));
+    assert_eq!(
+        parse_automount_root("[network]\nroot = /x\n[automount]\nenabled=true\nroot=\"/win/\" # drives\n"),
+        Some("/win/".to_owned()));
+    assert_eq!(parse_automount_root("[network]\nroot = /x\n"), None);
+    assert_eq!(parse_automount_root(""), None);
+}
+
+#[test]
+fn default_distro_parsing() {
+    let verbose = "  NAME      STATE           VERSION\r\n\
+                   \x20 Debian    Stopped         1\r\n\
+                   * Ubuntu-22.04 Running      2\r\n";
+    assert_eq!(parse_default_distro(verbose), Some("Ubuntu-22.04".to_owned()));
+    let list = "Windows Subsystem for Linux Distributions:\r\n\
+                Ubuntu (Default)\r\n\
+                Debian\r\n";
+    assert_eq!(parse_default_distro(list), Some("Ubuntu".to_owned()));
+    let utf16: Vec<u8> = list.encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes().to_vec()).collect();
+    assert_eq!(parse_default_distro(&decode_wsl_output(&utf16)), Some("Ubuntu".to_owned()));
+    // `wsl --list --quiet` has no marker
+    assert_eq!(parse_default_distro("Ubuntu\r\nDebian\r\n"), None);
+    assert_eq!(parse_default_distro(
+        "Windows Subsystem for Linux has no installed distributions.\r\n"), None);
+    assert_eq!(parse_default_distro(""), None);
+}
+
+#[test]
+fn wsl_version_parsing() {
+    let list = "  NAME      STATE           VERSION\r\n\
+                * Ubuntu    Running         2\r\n\
+                \x20 Debian    Stopped         1\r\n";
+    assert_eq!(parse_wsl_version(list, None), Some(WslVersion::Wsl2));
+    assert_eq!(parse_wsl_version(list, Some("debian")), Some(WslVersion::Wsl1));
+    assert_eq!(parse_wsl_version(list, Some("Arch")), None);
+    assert_eq!(parse_wsl_version("", None), None);
+    let utf16: Vec<u8> = "  NAME STATE VERSION\n* Ubuntu Running 2\n"
+        .encode_utf16().flat_map(|unit| unit.to_le_bytes().to_vec()).collect();
+    assert_eq!(parse_wsl_version(&decode_wsl_output(&utf16), None), Some(WslVersion::Wsl2));
+    assert_eq!(WslVersion::Wsl1.number(), 1);
+}
+
+#[test]
+fn win_to_unix_path_trans() {
+    assert_eq!(
+        translate_path_to_unix("d:\\test\\file.txt"),
+        "/mnt/d/test/file.txt");
+    assert_eq!(
+        translate_path_to_unix("C:\\Users\\test\\a space.txt"),
+        "/mnt/c/Users/test/a space.txt");
+}
+
+#[test]
+fn unix_to_win_path_trans() {
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/d/some path/a file.md"),
+        b"D:\\some path\\a file.md");
+    assert_eq!(
+        &*translate_path_to_win(b"origin  /mnt/c/path/ (fetch)"),
+        b"origin  C:\\path\\ (fetch)");
+    let multiline = b"mirror  /mnt/c/other/ (fetch)\nmirror  /mnt/c/other/ (push)\n";
+    let multiline_result = b"mirror  C:\\other\\ (fetch)\nmirror  C:\\other\\ (push)\n";
+    assert_eq!(
+        &*translate_path_to_win(&multiline[..]),
+        &multiline_result[..]);
+}
+
+#[test]
+fn stderr_path_translation() {
+    let mut stderr = Vec::new();
+    translate_lines(&b"fatal: '/mnt/d/foo' does not exist\n"[..], &mut stderr)
+        .unwrap();
+    assert_eq!(&stderr[..], &b"fatal: 'D:\\foo' does not exist\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win_with(&wslpath_regex("/mnt", None),
+                                     b"fatal: '/mnt/d/foo' does not exist\n",
+                                     PathSeparator::Slash),
+        &b"fatal: 'D:/foo' does not exist\n"[..]);
+}
+
+#[test]
+fn live_progress_lines() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Progress {
+        lines: Vec<&'static [u8]>,
+        written: Rc<RefCell<Vec<Vec<u8>>>>,
+    }
+
+    impl io::Read for Progress {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            // all lines read so far must be written before reading on
+            let read = 3 - self.lines.len();
+            assert_eq!(self.written.borrow().len(), read);
+            if self.lines.is_empty() {
+                return Ok(0);
+            }
+            let line = self.lines.remove(0);
+            buf[..line.len()].copy_from_slice(line);
+            Ok(line.len())
+        }
+    }
+
+    struct Recorder(Rc<RefCell<Vec<Vec<u8>>>>);
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let written = Rc::new(RefCell::new(Vec::new()));
+    let input = Progress {
+        lines: vec![&b"Receiving objects:  50% (1/2)\r"[..],
+                    &b"Receiving objects: 100% (2/2), done.\n"[..],
+                    &b"Cloning into '/mnt/c/repo'\r"[..]],
+        written: written.clone(),
+    };
+    translate_lines(io::BufReader::new(input), &mut Recorder(written.clone())).unwrap();
+    assert_eq!(&written.borrow()[..], &[
+        b"Receiving objects:  50% (1/2)\r".to_vec(),
+        b"Receiving objects: 100% (2/2), done.\n".to_vec(),
+        b"Cloning into 'C:\\repo'\r".to_vec()][..]);
+}
+
+#[test]
+fn streamed_path_translation() {
+    let mut input = Vec::new();
+    for i in 0..10000 {
+        input.extend_from_slice(
+            format!("{} /mnt/c/some/path/{} and /mnt/d/other\n", i, i).as_bytes());
+    }
+    // the last line has no trailing newline
+    input.extend_from_slice(b"origin  /mnt/e/repo/ (push)");
+    let mut output = Vec::new();
+    translate_lines(&input[..], &mut output).unwrap();
+    assert_eq!(&output[..], &*translate_path_to_win(&input));
+}
+
+#[test]
+fn patch_list_translation() {
+    // the files written by `git format-patch -o C:\patches`
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/c/patches/0001-foo.patch\n/mnt/c/patches/0002-bar.patch\n"),
+        &b"C:\\patches\\0001-foo.patch\nC:\\patches\\0002-bar.patch\n"[..]);
+}
+
+#[test]
+fn log_stat_translation() {
+    // `git log --stat` with paths outside of the work tree, e.g. with an
+    // absolute work tree, next to relative paths, which are kept
+    assert_eq!(
+        &*translate_path_to_win(b" /mnt/c/repo/src/main.rs | 3 +-\n src/lib.rs          | 12 ++++++\n"),
+        &b" C:\\repo\\src\\main.rs | 3 +-\n src/lib.rs          | 12 ++++++\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b" /mnt/c/my repo/a.txt | 1 +\n 1 file changed, 1 insertion(+)\n"),
+        &b" C:\\my repo\\a.txt | 1 +\n 1 file changed, 1 insertion(+)\n"[..]);
+}
+
+#[test]
+fn apply_reject_translation() {
+    // `git apply --reject` with an absolute --directory
+    assert_eq!(
+        &*translate_path_to_win(b"Checking patch /mnt/c/t/a.c...\nApplying patch /mnt/c/t/a.c with 1 reject...\nRejected hunk #1.\n"),
+        &b"Checking patch C:\\t\\a.c...\nApplying patch C:\\t\\a.c with 1 reject...\nRejected hunk #1.\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"error: cannot open /mnt/c/t/a.c.rej: Permission denied\n"),
+        &b"error: cannot open C:\\t\\a.c.rej: Permission denied\n"[..]);
+}
+
+#[test]
+fn tab_field_translation() {
+    // `git ls-tree -r HEAD`, with the path as last field after a tab
+    assert_eq!(
+        &*translate_path_to_win(b"100644 blob 1234abcd\t/mnt/c/repo/my dir/a b.txt\n"),
+        &b"100644 blob 1234abcd\tC:\\repo\\my dir\\a b.txt\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"100644 blob 1234abcd      42\t/mnt/c/a (1).txt\x00"),
+        &b"100644 blob 1234abcd      42\tC:\\a (1).txt\x00"[..]);
+    // quoted by git
+    assert_eq!(
+        &*translate_path_to_win(b"100644 blob 1234abcd\t\"/mnt/c/a\\tb\"\n"),
+        &b"100644 blob 1234abcd\t\"C:\\\\a\\tb\"\n"[..]);
+    // `git remote -v` keeps the kind of the remote after the path
+    assert_eq!(
+        &*translate_path_to_win(b"origin\t/mnt/c/repo (fetch)\n"),
+        &b"origin\tC:\\repo (fetch)\n"[..]);
+    // `git cat-file --batch-check='%(objectname) %(objecttype) %(rest)'`
+    assert_eq!(
+        &*translate_path_to_win(b"1234abcd blob /mnt/c/repo/a.txt\n"),
+        &b"1234abcd blob C:\\repo\\a.txt\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"HEAD:/mnt/c/x missing\n"),
+        &b"HEAD:C:\\x missing\n"[..]);
+}
+
+#[test]
+fn worktree_list_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/c/repo      abc1234 [main]\n/mnt/c/wt/feature  def5678 [feat/x]\n"),
+        &b"C:\\repo      abc1234 [main]\nC:\\wt\\feature  def5678 [feat/x]\n"[..]);
+    // `git worktree list --porcelain -z`
+    assert_eq!(
+        &*translate_path_to_win(b"worktree /mnt/c/repo\x00HEAD abc1234\x00branch refs/heads/main\x00\x00"),
+        &b"worktree C:\\repo\x00HEAD abc1234\x00branch refs/heads/main\x00\x00"[..]);
+}
+
+#[test]
+fn submodule_status_translation() {
+    // the status of each submodule precedes its commit
+    assert_eq!(
+        &*translate_path_to_win(b" abc123 /mnt/c/sub (v1.0)\n+def456 /mnt/c/sub (heads/main)\n"),
+        &b" abc123 C:\\sub (v1.0)\n+def456 C:\\sub (heads/main)\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b" abc123 /mnt/c/my libs/sub (remotes/origin/HEAD)\n"),
+        &b" abc123 C:\\my libs\\sub (remotes/origin/HEAD)\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"-abc123 /mnt/d/lib\nUabc123 /mnt/d/lib\n"),
+        &b"-abc123 D:\\lib\nUabc123 D:\\lib\n"[..]);
+    assert!(matches!(translate_path_to_win(b" abc123 libs/lib (v1.0)\n"), Cow::Borrowed(_)));
+}
+
+#[test]
+fn mid_line_path_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"fatal: not a git repository: /mnt/c/foo/.git"),
+        &b"fatal: not a git repository: C:\\foo\\.git"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"file:/mnt/c/Users/me/.gitconfig\tuser.name=me"),
+        &b"file:C:\\Users\\me\\.gitconfig\tuser.name=me"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"'/mnt/c/a' and \"/mnt/d/b\" or /mnt/e/c:/mnt/f/d"),
+        &b"'C:\\a' and \"D:\\\\b\" or E:\\c:F:\\d"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"/home/me/mnt/c/foo ~/mnt/d/bar"),
+        &b"/home/me/mnt/c/foo ~/mnt/d/bar"[..]);
+}
+
+#[test]
+fn prefixed_path_translation() {
+    // e.g. `git clean -n` with a work tree outside of the current directory
+    assert_eq!(
+        &*translate_path_to_win(b"Would remove /mnt/c/x"),
+        &b"Would remove C:\\x"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"Removing /mnt/c/build/out dir/"),
+        &b"Removing C:\\build\\out dir\\"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"\tmodified:   /mnt/d/src/main.rs"),
+        &b"\tmodified:   D:\\src\\main.rs"[..]);
+    assert!(matches!(translate_path_to_win(b"Would remove foo/bar"), Cow::Borrowed(_)));
+}
+
+#[test]
+fn show_origin_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"file:/mnt/c/Users/me/.gitconfig\tuser.name=me\n"),
+        &b"file:C:\\Users\\me\\.gitconfig\tuser.name=me\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"file:///mnt/d/repo/.git/config\tcore.bare=false\n"),
+        &b"file:///D:/repo/.git/config\tcore.bare=false\n"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"file:/home/me/.gitconfig\tuser.name=me\n"),
+        &b"file:/home/me/.gitconfig\tuser.name=me\n"[..]);
+}
+
+#[test]
+fn quoted_path_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"\"/mnt/c/a b.txt\""),
+        &b"\"C:\\\\a b.txt\""[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"\"/mnt/c/na\\303\\257ve\\t.txt\" -> /mnt/d/b.txt"),
+        &b"\"C:\\\\na\\303\\257ve\\t.txt\" -> D:\\b.txt"[..]);
+    // no other path inside a quoted path
+    assert_eq!(
+        &*translate_path_to_win(b"\"/mnt/c/a /mnt/d/b \\\"x\\\" /mnt/e\" /mnt/f/g"),
+        &b"\"C:\\\\a \\\\mnt\\\\d\\\\b \\\"x\\\" \\\\mnt\\\\e\" F:\\g"[..]);
+    assert_eq!(
+        &*translate_path_to_win(b"\"file:///mnt/c/repo.git\""),
+        &b"\"file:///C:/repo.git\""[..]);
+    // without closing quote
+    assert_eq!(
+        &*translate_path_to_win(b"\"/mnt/c/a b"),
+        &b"\"C:\\a b"[..]);
+}
+
+#[test]
+fn rev_parse_path_translation() {
+    let translate = |output: &[u8]| -> Vec<u8> {
+        let mut translated = Vec::new();
+        translate_records(output, &mut translated, b'\n', LineEnding::Keep).unwrap();
+        translated
+    };
+    // --show-toplevel
+    assert_eq!(translate(b"/mnt/c/my repo\n"), b"C:\\my repo\n");
+    // --git-dir, absolute in a subdirectory, relative at the top level
+    assert_eq!(translate(b"/mnt/c/repo/.git\n"), b"C:\\repo\\.git\n");
+    assert_eq!(translate(b".git\n"), b".git\n");
+    // --git-common-dir of a worktree, relative in the main worktree
+    assert_eq!(translate(b"/mnt/d/repo/.git\n"), b"D:\\repo\\.git\n");
+    assert_eq!(translate(b"../.git\n"), b"../.git\n");
+    // --absolute-git-dir
+    assert_eq!(translate(b"/mnt/c/repo/.git/worktrees/wt\n"),
+               b"C:\\repo\\.git\\worktrees\\wt\n");
+    // repositories inside WSL have no Windows path
+    assert_eq!(translate(b"/home/me/repo\n"), b"/home/me/repo\n");
+}
+
+#[test]
+fn grep_output_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/c/a.rs:10:foo"),
+        b"C:\\a.rs:10:foo");
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/c/src/a.rs:10:5:let x = \"/mnt/d/b\";"),
+        b"C:\\src\\a.rs:10:5:let x = \"D:\\\\b\";");
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/c/a.rs:3:see /mnt/d/b.rs:4"),
+        b"C:\\a.rs:3:see D:\\b.rs:4");
+    // with --null, the path is followed by NUL instead of `:`
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/c/a.rs\x0010\x00foo"),
+        b"C:\\a.rs\x0010\x00foo");
+}
+
+#[test]
+fn colored_output_translation() {
+    // `git -c color.ui=always status` and `diff`, with the path right
+    // after the color at the start of the line, or after the prefix
+    assert_eq!(
+        &*translate_path_to_win(b"\x1b[31m/mnt/c/repo/new.txt\x1b[m\n"),
+        b"\x1b[31mC:\\repo\\new.txt\x1b[m\n");
+    assert_eq!(
+        &*translate_path_to_win(b"\t\x1b[1;32mnew file:   /mnt/c/my repo/a.txt\x1b[m\n"),
+        b"\t\x1b[1;32mnew file:   C:\\my repo\\a.txt\x1b[m\n");
+    assert_eq!(
+        &*translate_path_to_win(b"\x1b[1m--- /mnt/c/a/x.rs\x1b[m\n\x1b[1m+++ /mnt/d/b/x.rs\x1b[m\n"),
+        b"\x1b[1m--- C:\\a\\x.rs\x1b[m\n\x1b[1m+++ D:\\b\\x.rs\x1b[m\n");
+    assert_eq!(
+        &*translate_path_to_win(b"\x1b[1m\x1b[31m/mnt/c/a\x1b[m \x1b[33m/mnt/c/b/c\x1b[m"),
+        b"\x1b[1m\x1b[31mC:\\a\x1b[m \x1b[33mC:\\b\\c\x1b[m");
+    // no path, but a word ending with `m`
+    assert_eq!(&*translate_path_to_win(b"x[1m/mnt/c/a"), b"x[1m/mnt/c/a");
+}
+
+#[test]
+fn colored_grep_lines_translation() {
+    let mut translated = Vec::new();
+    translate_grep_lines(
+        &b"\x1b[35m/mnt/c/a.rs\x1b[m\x1b[36m:\x1b[m\x1b[32m42\x1b[m\x1b[36m:\x1b[m/mnt/c/x\n"[..],
+        &mut translated, LineEnding::Keep).unwrap();
+    assert_eq!(&translated[..],
+               &b"\x1b[35mC:\\a.rs\x1b[m\x1b[36m:\x1b[m\x1b[32m42\x1b[m\x1b[36m:\x1b[m/mnt/c/x\n"[..]);
+}
+
+#[test]
+fn grep_lines_translation() {
+    let translate = |output: &[u8]| -> Vec<u8> {
+        let mut translated = Vec::new();
+        translate_grep_lines(output, &mut translated, LineEnding::Keep).unwrap();
+        translated
+    };
+    // -n
+    assert_eq!(translate(b"/mnt/c/src/main.rs:42:let p = \"/mnt/d/x\";\n"),
+               b"C:\\src\\main.rs:42:let p = \"/mnt/d/x\";\n");
+    // -n --column
+    assert_eq!(translate(b"/mnt/c/src/main.rs:42:13:matched text\n"),
+               b"C:\\src\\main.rs:42:13:matched text\n");
+    // multiple matches and context lines
+    assert_eq!(
+        translate(b"/mnt/c/a.rs:1:foo\n/mnt/c/a.rs-2-/mnt/c/bar\n--\n/mnt/d/b.rs:7:foo\n"),
+        b"C:\\a.rs:1:foo\nC:\\a.rs-2-/mnt/c/bar\n--\nD:\\b.rs:7:foo\n");
+    // -z
+    assert_eq!(translate(b"/mnt/c/a.rs\x0042\x0013\x00/mnt/c/x\n"),
+               b"C:\\a.rs\x0042\x0013\x00/mnt/c/x\n");
+    // relative paths and a missing final newline
+    assert_eq!(translate(b"src/a.rs:1:foo\n/mnt/c/b.rs:2:bar"),
+               b"src/a.rs:1:foo\nC:\\b.rs:2:bar");
+}
+
+#[test]
+fn rename_path_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"R  /mnt/c/old.txt -> /mnt/c/new.txt"),
+        b"R  C:\\old.txt -> C:\\new.txt");
+    assert_eq!(
+        &*translate_path_to_win(b"R  \"/mnt/c/old name.txt\" -> \"/mnt/d/new name.txt\""),
+        b"R  \"C:\\\\old name.txt\" -> \"D:\\\\new name.txt\"");
+    // with -z, the paths are separate records, the new one first
+    let mut output = Vec::new();
+    translate_records(
+        &b"R  /mnt/c/new.txt\0/mnt/c/old.txt\0"[..], &mut output, b'\0', LineEnding::Keep).unwrap();
+    assert_eq!(&output[..], &b"R  C:\\new.txt\0C:\\old.txt\0"[..]);
+}
+
+#[test]
+fn nul_separated_path_translation() {
+    let mut output = Vec::new();
+    translate_records(
+        &b"/mnt/c/path1\0 M /mnt/d/path2\0"[..], &mut output, b'\0', LineEnding::Keep).unwrap();
+    assert_eq!(&output[..], &b"C:\\path1\0 M D:\\path2\0"[..]);
+}
+
+#[test]
+fn binary_output_passthrough() {
+    let binary = b"PK\x03\x04\0\0/mnt/c/file.txt\n\x01\x02/mnt/d/x\0";
+    let mut output = Vec::new();
+    translate_records(&binary[..], &mut output, b'\n', LineEnding::Keep).unwrap();
+    assert_eq!(&output[..], &binary[..]);
+
+    let mut output = Vec::new();
+    translate_records(&b"/mnt/c/file.txt\n\t/mnt/d/x\n"[..], &mut output, b'\n', LineEnding::Keep).unwrap();
+    assert_eq!(&output[..], &b"C:\\file.txt\n\tD:\\x\n"[..]);
+
+    assert!(looks_binary(b"\x01\x02\x03\x04 abc", b'\0'));
+    assert!(!looks_binary(b"a\0b\0", b'\0'));
+    assert!(!looks_binary(b"\x1b[31mred\x1b[m\r\n", b'\n'));
+    assert!(!looks_binary(b"", b'\n'));
+}
+
+#[test]
+fn capped_output_passthrough() {
+    // a record longer than the cap and everything after it is passed on
+    let long = b"/mnt/c/a\n/mnt/c/0123456789abcdef/mnt/c/x\n/mnt/c/b\n";
+    let mut output = Vec::new();
+    assert!(translate_records_capped(&long[..], &mut output, b'\n', LineEnding::Crlf, 16)
+        .unwrap());
+    assert_eq!(&output[..], &b"C:\\a\r\n/mnt/c/0123456789abcdef/mnt/c/x\n/mnt/c/b\n"[..]);
+    // records up to the cap are translated
+    let mut output = Vec::new();
+    assert!(!translate_records_capped(&b"/mnt/c/a\0/mnt/c/b\0"[..], &mut output, b'\0',
+                                      LineEnding::Keep, 9).unwrap());
+    assert_eq!(&output[..], &b"C:\\a\0C:\\b\0"[..]);
+    let mut output = Vec::new();
+    assert!(translate_grep_lines_capped(&b"/mnt/c/a:1:x\n/mnt/c/b:2:yyyyyy\n"[..], &mut output,
+                                        LineEnding::Keep, 13).unwrap());
+    assert_eq!(&output[..], &b"C:\\a:1:x\n/mnt/c/b:2:yyyyyy\n"[..]);
+}
+
+#[test]
+fn output_line_endings() {
+    let translate = |eol: LineEnding, separator: u8| -> Vec<u8> {
+        let mut output = Vec::new();
+        translate_records(&b"/mnt/c/a\r\nb\n\nc"[..], &mut output, separator, eol).unwrap();
+        output
+    };
+    assert_eq!(translate(LineEnding::Keep, b'\n'), b"C:\\a\r\nb\n\nc");
+    assert_eq!(translate(LineEnding::Lf, b'\n'), b"C:\\a\nb\n\nc");
+    assert_eq!(translate(LineEnding::Crlf, b'\n'), b"C:\\a\r\nb\r\n\r\nc");
+    // not with -z
+    assert_eq!(translate(LineEnding::Crlf, b'\0'), b"C:\\a\r\nb\n\nc");
+    let mut output = Vec::new();
+    translate_grep_lines(&b"/mnt/c/a:1:x\r\n"[..], &mut output, LineEnding::Lf).unwrap();
+    assert_eq!(output, b"C:\\a:1:x\n");
+    assert_eq!(LineEnding::parse("crlf"), Some(LineEnding::Crlf));
+    assert_eq!(LineEnding::parse("cr"), None);
+}
+
+#[test]
+fn input_line_endings() {
+    let input = &b"subject\r\n\r\nbody with \r inside\r\nlast"[..];
+    let mut output = Vec::new();
+    copy_input(input, &mut output, true, |_| None).unwrap();
+    assert_eq!(&output[..], &b"subject\n\nbody with \r inside\nlast"[..]);
+    let mut output = Vec::new();
+    copy_input(input, &mut output, false, |_| None).unwrap();
+    assert_eq!(&output[..], input);
+}
+
+#[test]
+fn input_transcoding() {
+    // a few characters of code page 437, as piped from the console
+    let cp437 = |bytes: &[u8]| -> Option<String> {
+        bytes.iter().map(|&c| match c {
+            0x81 => Some('ü'),
+            0x84 => Some('ä'),
+            0xe1 => Some('ß'),
+            c if c < 0x80 => Some(char::from(c)),
+            _ => None
+        }).collect()
+    };
+    assert_eq!(&*transcode_line(b"Gr\x81\xe1e\n", cp437), "Grüße\n".as_bytes());
+    assert_eq!(&*transcode_line("Grüße\n".as_bytes(), cp437), "Grüße\n".as_bytes());
+    assert_eq!(&*transcode_line(b"\xff\n", cp437), &b"\xff\n"[..]);
+    let mut output = Vec::new();
+    copy_input(&b"K\x84se\r\n\r\nok\r\n"[..], &mut output, true, cp437).unwrap();
+    assert_eq!(&output[..], "Käse\n\nok\n".as_bytes());
+}
+
+#[test]
+fn mount_root_translation() {
+    for &(mount_root, unix_path) in &[
+            ("/mnt", "/mnt/c/foo"),
+            ("/mnt/", "/mnt/c/foo"),
+            ("/", "/c/foo"),
+            ("/windir", "/windir/c/foo")] {
+        let mount_root = normalize_mount_root(mount_root);
+        assert_eq!(
+            format!("{}/foo", get_prefix_for_drive_at(&mount_root, "c")),
+            unix_path);
+        let line = format!("origin  {} (fetch)", unix_path);
+        assert_eq!(
+            &*translate_path_to_win_with(
+                &wslpath_regex(&mount_root, None), line.as_bytes(), PathSeparator::Slash),
+            &b"origin  C:/foo (fetch)"[..]);
+    }
+    assert_eq!(
+        &*translate_path_to_win_with(
+            &wslpath_regex("/windir", None), b"/mnt/c/foo", PathSeparator::Slash),
+        &b"/mnt/c/foo"[..]);
+}
+
+#[test]
+fn drive_allowlist() {
+    assert_eq!(parse_drives("cd"), Some("cd".to_owned()));
+    assert_eq!(parse_drives("D, c, d"), Some("cd".to_owned()));
+    assert_eq!(parse_drives(" "), None);
+    let drives = parse_drives("cd");
+    assert!(is_listed_drive(drives.as_deref(), b'C'));
+    assert!(is_listed_drive(drives.as_deref(), b'd'));
+    assert!(!is_listed_drive(drives.as_deref(), b'Z'));
+    assert!(is_listed_drive(None, b'Z'));
+
+    let wslpath_re = wslpath_regex("/mnt", drives.as_deref());
+    let translate = |line: &[u8]| {
+        translate_path_to_win_with(&wslpath_re, line, PathSeparator::Backslash).into_owned()
+    };
+    assert_eq!(translate(b"/mnt/c/x /mnt/z/y \"/mnt/z/q\" \"/mnt/D/q\""),
+               b"C:\\x /mnt/z/y \"/mnt/z/q\" \"D:\\\\q\"".to_vec());
+    let leading_re = leading_wslpath_regex("/mnt", drives.as_deref());
+    assert_eq!(&*translate_leading_path_to_win_with(&leading_re, b"/mnt/z/a.rs:1:x",
+                                                    PathSeparator::Backslash),
+               &b"/mnt/z/a.rs:1:x"[..]);
+    assert_eq!(&*translate_leading_path_to_win_with(&leading_re, b"/mnt/c/a.rs:1:x",
+                                                    PathSeparator::Backslash),
+               &b"C:\\a.rs:1:x"[..]);
+}
+
+#[test]
+fn output_path_separator() {
+    let wslpath_re = wslpath_regex("/mnt", None);
+    let translate = |line: &'static [u8], separator| -> Vec<u8> {
+        translate_path_to_win_with(&wslpath_re, line, separator).into_owned()
+    };
+    assert_eq!(translate(b"origin  /mnt/c/my repo/ (fetch)", PathSeparator::Backslash),
+               b"origin  C:\\my repo\\ (fetch)");
+    assert_eq!(translate(b"origin  /mnt/c/my repo/ (fetch)", PathSeparator::Slash),
+               b"origin  C:/my repo/ (fetch)");
+    assert_eq!(translate(b"\"/mnt/c/a\\tb\"", PathSeparator::Slash), b"\"C:/a\\tb\"");
+    assert_eq!(translate(b"file:///mnt/c/repo.git", PathSeparator::Backslash),
+               b"file:///C:/repo.git");
+    let leading_re = leading_wslpath_regex("/mnt", None);
+    assert_eq!(
+        &*translate_leading_path_to_win_with(
+            &leading_re, b"/mnt/c/src/a.rs:1:x/y", PathSeparator::Slash),
+        b"C:/src/a.rs:1:x/y");
+    assert_eq!(
+        &*translate_leading_path_to_win_with(
+            &leading_re, b"/mnt/c/src/a.rs-2-x/y", PathSeparator::Backslash),
+        b"C:\\src\\a.rs-2-x/y");
+    assert_eq!(PathSeparator::parse("\\"), Some(PathSeparator::Backslash));
+    assert_eq!(PathSeparator::parse("/"), Some(PathSeparator::Slash));
+    assert_eq!(PathSeparator::parse("slash"), None);
+}
+
+#[test]
+fn startup_output_removal() {
+    let output = format!(
+        "Agent pid 123\nWelcome!\n{}\norigin  /mnt/c/repo (fetch)\n", OUTPUT_MARKER);
+    let mut input = output.as_bytes();
+    assert!(skip_to_marker(&mut input, OUTPUT_MARKER).unwrap().is_empty());
+    let mut translated = Vec::new();
+    translate_lines(input, &mut translated).unwrap();
+    assert_eq!(&translated[..], &b"origin  C:\\repo (fetch)\n"[..]);
+
+    let mut input = &b"Agent pid 123\n"[..];
+    assert_eq!(
+        &skip_to_marker(&mut input, OUTPUT_MARKER).unwrap()[..],
+        &b"Agent pid 123\n"[..]);
+}
+
+#[test]
+fn no_path_translation() {
+    assert_eq!(
+        &*translate_path_to_win(b"/mnt/other/file.sh"),
+        b"/mnt/other/file.sh");
+}
+
+#[test]
+fn relative_path_translation() {
+    assert_eq!(
+        translate_path_to_unix(".\\src\\main.rs"),
+        "./src/main.rs");
+}
+
+#[test]
+fn long_argument_path_translation() {
+    assert_eq!(
+        translate_path_to_unix("--file=C:\\some\\path.txt"),
+        "--file=/mnt/c/some/path.txt");
+}
+
+#[test]
+fn long_option_splitting() {
+    assert_eq!(split_long_option("--git-dir=C:\\a\\b"), ("--git-dir=", "C:\\a\\b"));
+    assert_eq!(split_long_option("--pretty=format:%H=%s"), ("--pretty=", "format:%H=%s"));
+    assert_eq!(split_long_option("--version"), ("", "--version"));
+    assert_eq!(split_long_option("-c"), ("", "-c"));
+    assert_eq!(split_long_option("a=b"), ("", "a=b"));
+}
+
+#[test]
+fn long_option_value_translation() {
+    assert_eq!(
+        translate_path_to_unix("--git-dir=C:\\a\\b"),
+        "--git-dir=/mnt/c/a/b");
+    assert_eq!(
+        translate_path_to_unix("--pretty=format:%H"),
+        "--pretty=format:%H");
+    assert_eq!(
+        translate_path_to_unix("-C"),
+        "-C");
+    assert_eq!(
+        translate_path_to_unix("C:\\x"),
+        "/mnt/c/x");
+}
+
+#[test]
+fn git_command_detection() {
+    assert_eq!(find_git_command(&string_args(&["rev-parse", "--show-toplevel"])),
+               Some("rev-parse"));
+    assert_eq!(find_git_command(&string_args(&["-C", "status", "-c", "a=b", "log"])), Some("log"));
+    assert_eq!(find_git_command(&string_args(&["--no-pager", "status"])), Some("status"));
+    assert_eq!(find_git_command(&string_args(&["--version"])), None);
+    assert_eq!(find_git_command_index(&string_args(&["-C", "sub", "commit"])), Some(2));
+}
+
+#[test]
+fn config_option_translation() {
+    assert_eq!(translate_config_to_unix("core.hooksPath=C:\\h"), "core.hooksPath=/mnt/c/h");
+    assert_eq!(translate_config_to_unix("user.name=Firstname"), "user.name=Firstname");
+    assert_eq!(translate_config_to_unix("key=a=b"), "key=a=b");
+    assert_eq!(translate_config_to_unix("key"), "key");
+    assert_eq!(translate_config_to_unix("includeIf.gitdir:C:/x/.path=C:\\cfg"),
+               "includeIf.gitdir:/mnt/c/x/.path=/mnt/c/cfg");
+    assert_eq!(translate_config_to_unix("includeIf.onbranch:main.path=a"),
+               "includeIf.onbranch:main.path=a");
+    assert_eq!(
+        translate_args_to_unix(os_args(&["-c", "core.hooksPath=C:\\h", "status"])),
+        os_args(&["-c", "core.hooksPath=/mnt/c/h", "status"]));
+}
+
+#[test]
+fn message_arg_translation() {
+    for message_args in &[
+            &["commit", "-m", "C:\\x"][..],
+            &["commit", "-am", "fixed C:\\src\\bug.rs"],
+            &["commit", "--message=fixed C:\\src\\bug.rs"],
+            &["commit", "-mC:\\x"],
+            &["commit", "-m", "--", "-m", "C:\\x"],
+            &["tag", "-a", "v1", "-m", "built in D:\\out"],
+            &["notes", "add", "-m", "C:\\x"],
+            &["commit", "-C", "HEAD", "--", "C:"]] {
+        assert_eq!(translate_args_to_unix(os_args(message_args)), os_args(message_args));
+    }
+    assert_eq!(translate_args_to_unix(os_args(&["add", "C:\\x"])), os_args(&["add", "/mnt/c/x"]));
+    assert_eq!(translate_args_to_unix(os_args(&["commit", "-m", "C:\\x", "C:\\y"])),
+               os_args(&["commit", "-m", "C:\\x", "/mnt/c/y"]));
+}
+
+#[test]
+fn worktree_arg_translation() {
+    assert_eq!(translate_args_to_unix(os_args(&["worktree", "add", "..\\wt\\feature"])),
+               os_args(&["worktree", "add", "../wt/feature"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["worktree", "add", "-b", "x\\y", "..\\wt", "a\\b"])),
+        os_args(&["worktree", "add", "-b", "x\\y", "../wt", "a\\b"]));
+    assert_eq!(translate_args_to_unix(os_args(&["worktree", "move", "wt\\a", "wt\\b"])),
+               os_args(&["worktree", "move", "wt/a", "wt/b"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["worktree", "lock", "--reason", "a\\b", "wt\\a"])),
+        os_args(&["worktree", "lock", "--reason", "a\\b", "wt/a"]));
+    assert_eq!(translate_args_to_unix(os_args(&["worktree", "list", "a\\b"])),
+               os_args(&["worktree", "list", "a\\b"]));
+    assert_eq!(translate_args_to_unix(os_args(&["log", "add", "a\\b"])),
+               os_args(&["log", "add", "a\\b"]));
+}
+
+#[test]
+fn absolute_worktree_translation() {
+    assert_eq!(translate_args_to_unix(os_args(&["worktree", "add", "C:\\wt\\feature"])),
+               os_args(&["worktree", "add", "/mnt/c/wt/feature"]));
+}
+
+#[test]
+fn submodule_arg_translation() {
+    assert_eq!(
+        translate_args_to_unix(os_args(&["submodule", "add", "https://host/lib.git", "libs\\lib"])),
+        os_args(&["submodule", "add", "https://host/lib.git", "libs/lib"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["submodule", "add", "--name", "a\\b", "-b", "main",
+                                      "..\\lib.git", "libs\\lib"])),
+        os_args(&["submodule", "add", "--name", "a\\b", "-b", "main", "../lib.git", "libs/lib"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["submodule", "--quiet", "add", "git@host:lib.git"])),
+        os_args(&["submodule", "--quiet", "add", "git@host:lib.git"]));
+    assert_eq!(translate_args_to_unix(os_args(&["submodule", "status", "libs\\lib"])),
+               os_args(&["submodule", "status", "libs\\lib"]));
+}
+
+#[test]
+fn absolute_submodule_translation() {
+    assert_eq!(
+        translate_args_to_unix(
+            os_args(&["submodule", "add", "C:\\local\\mirror", "path\\in\\repo"])),
+        os_args(&["submodule", "add", "/mnt/c/local/mirror", "path/in/repo"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["submodule", "add", "--reference", "D:\\cache", "..\\x"])),
+        os_args(&["submodule", "add", "--reference", "/mnt/d/cache", "../x"]));
+}
+
+#[test]
+fn alias_arg_translation() {
+    // unknown commands, like aliases, get their arguments translated
+    // like any other command; paths in the alias itself are not seen
+    assert_eq!(translate_args_to_unix(os_args(&["myalias", "C:\\src", "-v"])),
+               os_args(&["myalias", "/mnt/c/src", "-v"]));
+    assert_eq!(translate_args_to_unix(os_args(&["-c", "x=y", "myalias", "--", "D:\\out"])),
+               os_args(&["-c", "x=y", "myalias", "--", "/mnt/d/out"]));
+    assert_eq!(translate_args_to_unix(os_args(&["myalias", "file:///C:/repo.git"])),
+               os_args(&["myalias", &translate_path_to_unix("file:///C:/repo.git")]));
+}
+
+#[test]
+fn literal_option_translation() {
+    for literal_args in &[
+            &["log", "--grep=C:\\x"][..],
+            &["log", "--grep", "C:\\x", "--author", "D:\\me"],
+            &["log", "--pretty=format:%H"],
+            &["log", "--format=C:\\%s"],
+            &["for-each-ref", "--format", "C:\\%(refname)"],
+            &["merge", "-m", "C:\\y"]] {
+        assert_eq!(translate_args_to_unix(os_args(literal_args)), os_args(literal_args));
+    }
+    assert_eq!(translate_args_to_unix(os_args(&["log", "--grep=x", "--", "C:\\z"])),
+               os_args(&["log", "--grep=x", "--", "/mnt/c/z"]));
+    assert_eq!(translate_args_to_unix(os_args(&["add", "C:\\z"])), os_args(&["add", "/mnt/c/z"]));
+}
+
+#[test]
+fn format_option_translation() {
+    // format strings which look like paths, before any pathspec
+    for format_args in &[
+            &["log", "--format=%H %s"][..],
+            &["log", "--format", "D:%h\\%an"],
+            &["log", "--pretty=tformat:C:\\%s", "--stat"],
+            &["show", "-s", "--format=\\\\%H"]] {
+        assert_eq!(translate_args_to_unix(os_args(format_args)), os_args(format_args));
+    }
+    assert_eq!(translate_args_to_unix(os_args(&["log", "--format", "C:%s", "--", "C:\\z"])),
+               os_args(&["log", "--format", "C:%s", "--", "/mnt/c/z"]));
+}
+
+#[test]
+fn literal_option_detection() {
+    let extra = vec!["--since".to_owned(), "-S".to_owned()];
+    assert!(is_literal_option("--grep", &[]));
+    assert!(!is_literal_option("--pretty", &[]));
+    assert!(has_literal_value("--pretty=format:%H", &[]));
+    assert!(!has_literal_value("--grep", &[]));
+    assert!(!has_literal_value("--file=C:\\x", &[]));
+    assert!(is_literal_option("--since", &extra));
+    assert!(has_literal_value("--since=C:", &extra));
+    assert!(is_literal_option("-S", &extra));
+    assert!(has_literal_value("-SC:\\x", &extra));
+    assert!(!has_literal_value("-S", &extra));
+}
+
+#[test]
+fn dir_option_translation() {
+    assert_eq!(translate_args_to_unix(os_args(&["-C", "sub\\dir", "status"])),
+               os_args(&["-C", "sub/dir", "status"]));
+    assert_eq!(translate_args_to_unix(os_args(&["-C", ".", "status"])),
+               os_args(&["-C", ".", "status"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["-c", "a=b", "-C", "x\\y", "log"])),
+        os_args(&["-c", "a=b", "-C", "x/y", "log"]));
+    // -C of git commands is not a directory
+    assert_eq!(translate_args_to_unix(os_args(&["commit", "-C", "a\\b"])),
+               os_args(&["commit", "-C", "a\\b"]));
+}
+
+#[test]
+fn output_file_translation() {
+    assert_eq!(
+        translate_args_to_unix(os_args(&["archive", "-o", "out\\repo.zip", "HEAD"])),
+        os_args(&["archive", "-o", "out/repo.zip", "HEAD"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["archive", "-o", "-", "HEAD"])),
+        os_args(&["archive", "-o", "-", "HEAD"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["-C", "repo", "bundle", "create", "a\\b.bundle", "HEAD"])),
+        os_args(&["-C", "repo", "bundle", "create", "a/b.bundle", "HEAD"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["log", "-o", "a\\b"])),
+        os_args(&["log", "-o", "a\\b"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["format-patch", "-o", "out\\patches", "HEAD~3"])),
+        os_args(&["format-patch", "-o", "out/patches", "HEAD~3"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["format-patch", "-oout\\patches", "HEAD~3"])),
+        os_args(&["format-patch", "-oout/patches", "HEAD~3"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["format-patch", "--output-directory=out\\patches"])),
+        os_args(&["format-patch", "--output-directory=out/patches"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["log", "-oout\\patches"])),
+        os_args(&["log", "-oout\\patches"]));
+}
+
+#[test]
+fn absolute_output_dir_translation() {
+    assert_eq!(
+        translate_args_to_unix(os_args(&["format-patch", "-o", "C:\\patches", "HEAD~3"])),
+        os_args(&["format-patch", "-o", "/mnt/c/patches", "HEAD~3"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["format-patch", "-oC:\\patches", "HEAD~3"])),
+        os_args(&["format-patch", "-o/mnt/c/patches", "HEAD~3"]));
+}
+
+#[test]
+fn apply_arg_translation() {
+    assert_eq!(
+        translate_args_to_unix(os_args(&["apply", "--directory=C:\\t", "C:\\patches\\fix.diff"])),
+        os_args(&["apply", "--directory=/mnt/c/t", "/mnt/c/patches/fix.diff"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["am", "--directory", "C:\\t", "-3", "C:\\mail.mbox"])),
+        os_args(&["am", "--directory", "/mnt/c/t", "-3", "/mnt/c/mail.mbox"]));
+}
+
+#[test]
+fn apply_directory_translation() {
+    // relative to the root of the work tree, so it need not exist here
+    assert_eq!(translate_args_to_unix(os_args(&["apply", "--directory=sub\\dir", "-p2"])),
+               os_args(&["apply", "--directory=sub/dir", "-p2"]));
+    assert_eq!(translate_args_to_unix(os_args(&["am", "--directory", "sub\\dir"])),
+               os_args(&["am", "--directory", "sub/dir"]));
+}
+
+#[test]
+fn config_file_translation() {
+    assert_eq!(
+        translate_args_to_unix(os_args(&["config", "-f", "sub\\.gitconfig", "a.b", "x\\y"])),
+        os_args(&["config", "-f", "sub/.gitconfig", "a.b", "x\\y"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["config", "--file=sub\\.gitconfig", "a.b"])),
+        os_args(&["config", "--file=sub/.gitconfig", "a.b"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["config", "user.email", "foo@bar"])),
+        os_args(&["config", "user.email", "foo@bar"]));
+}
+
+#[test]
+fn absolute_config_file_translation() {
+    assert_eq!(
+        translate_args_to_unix(os_args(&["config", "--file", "C:\\x\\.gitconfig", "user.name"])),
+        os_args(&["config", "--file", "/mnt/c/x/.gitconfig", "user.name"]));
+    assert_eq!(
+        translate_args_to_unix(os_args(&["config", "--file=D:\\y", "user.name"])),
+        os_args(&["config", "--file=/mnt/d/y", "user.name"]));
+}
+
+#[test]
+fn absolute_dir_option_translation() {
+    assert_eq!(translate_args_to_unix(os_args(&["-C", "C:\\project", "status"])),
+               os_args(&["-C", "/mnt/c/project", "status"]));
+}
+
+#[test]
+fn relative_path_slash_conversion() {
+    assert_eq!(convert_relative_path("src\\main.rs"), Some("src/main.rs".to_owned()));
+    assert_eq!(convert_relative_path("a\\b/c.txt"), Some("a/b/c.txt".to_owned()));
+    // not relative paths
+    assert_eq!(convert_relative_path("fix \\n handling"), None);
+    assert_eq!(convert_relative_path("use\\\\escapes"), None);
+    assert_eq!(convert_relative_path("C:\\src\\main.rs"), None);
+    assert_eq!(convert_relative_path("\\\\server\\share"), None);
+    assert_eq!(convert_relative_path("\\src"), None);
+    assert_eq!(convert_relative_path("src/main.rs"), None);
+    assert_eq!(convert_relative_path("%s\\n"), None);
+}
+
+#[test]
+fn separated_pathspec_translation() {
+    assert_eq!(translate_pathspec_to_unix("src\\file.rs", true), "src/file.rs");
+    assert_eq!(translate_pathspec_to_unix("my dir\\file.rs", true), "my dir/file.rs");
+    assert_eq!(translate_pathspec_to_unix(":!build\\out", true), ":!build/out");
+    assert_eq!(translate_pathspec_to_unix("src\\file.rs", false), "src\\file.rs");
+    // no options after `--`
+    assert_eq!(translate_pathspec_to_unix("--file=a\\b", true), "--file=a/b");
+    // paths of other options are only translated before `--`
+    assert_eq!(translate_args_to_unix(os_args(&["archive", "HEAD", "--", "-o", "x\\y"]))[4],
+               "x\\y");
+    assert_eq!(translate_args_to_unix(os_args(&["log", "--", "C:\\a.txt"])),
+               os_args(&["log", "--", "/mnt/c/a.txt"]));
+}
+
+#[test]
+fn separated_pathspec_relative_translation() {
+    assert_eq!(translate_args_to_unix(os_args(&["checkout", "--", "src\\lib.rs"])),
+               os_args(&["checkout", "--", "src/lib.rs"]));
+}
+
+#[test]
+fn path_list_translation() {
+    assert_eq!(translate_path_list_to_unix("C:\\a;D:\\b"), "/mnt/c/a:/mnt/d/b");
+    assert_eq!(translate_path_list_to_unix("C:\\a;"), "/mnt/c/a");
+    assert_eq!(translate_path_list_to_unix("C:\\objects"), "/mnt/c/objects");
+}
+
+#[test]
+fn env_var_expansion() {
+    let lookup = |name: &str| match name {
+        "USERPROFILE" => Some("C:\\Users\\me".to_owned()),
+        "ProgramFiles(x86)" => Some("C:\\Program Files (x86)".to_owned()),
+        _ => None
+    };
+    assert_eq!(expand_env_vars("%USERPROFILE%\\x", lookup), "C:\\Users\\me\\x");
+    assert_eq!(expand_env_vars("--file=%USERPROFILE%\\.gitconfig", lookup),
+               "--file=C:\\Users\\me\\.gitconfig");
+    assert_eq!(expand_env_vars("%ProgramFiles(x86)%\\Git", lookup),
+               "C:\\Program Files (x86)\\Git");
+    // undefined variables
+    assert_eq!(expand_env_vars("%UNDEFINED%\\x", lookup), "%UNDEFINED%\\x");
+    // no references
+    assert_eq!(expand_env_vars("--format=%h %s", lookup), "--format=%h %s");
+    assert_eq!(expand_env_vars("100%", lookup), "100%");
+    assert!(matches!(expand_env_vars("plain", lookup), Cow::Borrowed(_)));
+}
+
+#[test]
+fn env_var_path_arg_expansion() {
+    env::set_var("EXPANSION_TEST_DIR", "sub/dir");
+    // paths are expanded
+    assert_eq!(
+        translate_args_to_unix(os_args(&["worktree", "add", "%EXPANSION_TEST_DIR%"])),
+        os_args(&["worktree", "add", "sub/dir"]));
+    assert_eq!(translate_args_to_unix(os_args(&["log", "--", "%EXPANSION_TEST_DIR%"])),
+               os_args(&["log", "--", "sub/dir"]));
+    // messages, formats and patterns are not
+    for literal_args in &[
+            &["commit", "-m", "%EXPANSION_TEST_DIR%"][..],
+            &["commit", "-m", "100%EXPANSION_TEST_DIR%"],
+            &["log", "--format=%EXPANSION_TEST_DIR%%n"],
+            &["log", "--grep", "%EXPANSION_TEST_DIR%"],
+            &["show", "%EXPANSION_TEST_DIR%"]] {
+        assert_eq!(translate_args_to_unix(os_args(literal_args)), os_args(literal_args));
+    }
+}
+
+#[test]
+fn url_detection() {
+    assert!(is_url("a://host/path"));
+    assert!(is_url("https://github.com/andy-5/wslgit.git"));
+    assert!(is_url("git+ssh://host/repo"));
+    assert!(!is_url("c:/dir"));
+    assert!(!is_url("c:\\dir"));
+    assert!(!is_url("://host"));
+    assert!(!is_url("a b://host"));
+    assert_eq!(translate_path_to_unix("a://host/path"), "a://host/path");
+}
+
+#[test]
+fn single_letter_scheme_translation() {
+    assert_eq!(translate_path_to_unix("a://host"), "a://host");
+    assert_eq!(translate_path_to_unix("c:/dir"), "/mnt/c/dir");
+    assert_eq!(translate_path_to_unix("c:\\dir"), "/mnt/c/dir");
+}
+
+#[test]
+fn drive_path_detection() {
+    assert_eq!(find_drive_paths("C:\\tools\\diff.exe C:\\a.txt\td:/b.txt"), &[0, 18, 27]);
+    assert_eq!(find_drive_paths("diff C:/a"), &[5]);
+    assert!(find_drive_paths("xC:\\foo abc:\\def a://host c: d:x").is_empty());
+    assert_eq!(translate_path_to_unix("xC:\\foo bar"), "xC:\\foo bar");
+}
+
+#[test]
+fn multiple_paths_translation() {
+    assert_eq!(
+        translate_path_to_unix("--diff-cmd=C:\\tools\\diff.exe C:\\a.txt D:\\b.txt"),
+        "--diff-cmd=/mnt/c/tools/diff.exe /mnt/c/a.txt /mnt/d/b.txt");
+    assert_eq!(
+        translate_path_to_unix("C:\\Program Files\\diff.exe  D:\\x y\\b.txt"),
+        "/mnt/c/Program Files/diff.exe  /mnt/d/x y/b.txt");
+    assert_eq!(
+        translate_path_to_unix("mydiff C:\\a.txt"),
+        "mydiff /mnt/c/a.txt");
+}
+
+#[test]
+fn pathspec_magic_splitting() {
+    assert_eq!(split_pathspec_magic(":(exclude)src"), (":(exclude)", "src"));
+    assert_eq!(split_pathspec_magic(":(top,icase)a"), (":(top,icase)", "a"));
+    assert_eq!(split_pathspec_magic(":!src"), (":!", "src"));
+    assert_eq!(split_pathspec_magic(":^/:src"), (":^/:", "src"));
+    assert_eq!(split_pathspec_magic(":/"), (":/", ""));
+    assert_eq!(split_pathspec_magic(":(glob"), ("", ":(glob"));
+    assert_eq!(split_pathspec_magic("HEAD:src"), ("", "HEAD:src"));
+}
+
+#[test]
+fn pathspec_magic_translation() {
+    assert_eq!(
+        translate_path_to_unix(":(exclude)C:\\build"),
+        ":(exclude)/mnt/c/build");
+    assert_eq!(
+        translate_path_to_unix(":!D:\\x"),
+        ":!/mnt/d/x");
+    assert_eq!(
+        translate_path_to_unix(":/:C:\\top"),
+        ":/:/mnt/c/top");
+    assert_eq!(
+        translate_path_to_unix(":(glob)*.rs"),
+        ":(glob)*.rs");
+    assert_eq!(
+        translate_path_to_unix(":(icase)readme"),
+        ":(icase)readme");
+    assert_eq!(
+        translate_path_to_unix(":/fix typo"),
+        ":/fix typo");
+}
+
+#[test]
+fn file_url_translation() {
+    assert_eq!(
+        translate_path_to_unix("file:///C:/mirror/repo.git"),
+        "file:///mnt/c/mirror/repo.git");
+    assert_eq!(
+        translate_path_to_unix("file://d:\\mirror"),
+        "file:///mnt/d/mirror");
+    assert_eq!(
+        translate_path_to_unix("file://./foo"),
+        "file://./foo");
+    assert_eq!(
+        translate_path_to_unix("https://github.com/andy-5/wslgit.git"),
+        "https://github.com/andy-5/wslgit.git");
+    assert_eq!(
+        translate_path_to_unix("ssh://git@host/c:/repo.git"),
+        "ssh://git@host/c:/repo.git");
+    assert_eq!(
+        &*translate_path_to_win(b"origin  file:///mnt/c/mirror/repo.git (fetch)"),
+        b"origin  file:///C:/mirror/repo.git (fetch)");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_unicode_argument() {
+    use std::os::unix::ffi::OsStringExt;
+    let arg = OsString::from_vec(vec![b'f', 0xff, b'o']);
+    assert_eq!(translate_arg_to_unix(arg.clone()), arg);
+}
+
+#[cfg(windows)]
+#[test]
+fn non_unicode_argument() {
+    use std::os::windows::ffi::OsStringExt;
+    let arg = OsString::from_wide(&[0x66, 0xd800, 0x6f]);
+    assert_eq!(translate_arg_to_unix(arg.clone()), arg);
+}
+
+#[test]
+fn separator_normalization() {
+    assert_eq!(normalize_separators("C:\\\\foo"), "C:\\foo");
+    assert_eq!(normalize_separators("C:\\foo/bar\\baz"), "C:\\foo\\bar\\baz");
+    assert_eq!(normalize_separators("C:/foo//bar/"), "C:\\foo\\bar\\");
+    assert_eq!(normalize_separators("C:\\"), "C:\\");
+    assert_eq!(normalize_separators("\\\\server\\\\share"), "\\\\server\\share");
+    assert_eq!(normalize_separators("//server/share"), "\\\\server\\share");
+    assert_eq!(normalize_separators("\\\\?\\C:\\a/b"), "\\\\?\\C:\\a/b");
+}
+
+#[test]
+fn mixed_separator_translation() {
+    assert_eq!(translate_path_to_unix("C:\\\\foo"), "/mnt/c/foo");
+    assert_eq!(
+        translate_path_to_unix("C:\\foo/bar\\baz"),
+        "/mnt/c/foo/bar/baz");
+    assert_eq!(
+        translate_path_to_unix("C:/foo"),
+        translate_path_to_unix("C:\\foo"));
+    assert_eq!(translate_path_to_unix("C:\\"), "/mnt/c");
+}
+
+#[cfg(windows)]
+#[test]
+fn drive_relative_path_translation() {
+    let cwd = std::env::current_dir().unwrap();
+    let drive = cwd.to_str().unwrap()[..2].to_owned();
+    let cwd_unix = translate_path_to_unix(cwd.to_str().unwrap()).into_owned();
+    assert_eq!(
+        translate_path_to_unix(&format!("{}src", drive)),
+        format!("{}/src", cwd_unix));
+    assert_eq!(translate_path_to_unix(&drive), cwd_unix);
+    assert_eq!(
+        translate_path_to_unix(&format!("{}does-not-exist", drive)),
+        format!("{}does-not-exist", drive));
+    assert_eq!(translate_path_to_unix("C:\\foo"), "/mnt/c/foo");
+}
+
+#[test]
+fn untranslated_argument_borrowed() {
+    match translate_path_to_unix("--version") {
+        Cow::Borrowed(arg) => assert_eq!(arg, "--version"),
+        Cow::Owned(_) => panic!("untranslated argument was copied")
+    }
+    match translate_path_to_unix("HEAD~1") {
+        Cow::Borrowed(_) => {},
+        Cow::Owned(_) => panic!("untranslated argument was copied")
+    }
+}
+
+#[test]
+fn repeated_translation() {
+    // translating an argument again must not change it anymore
+    for &arg in &[
+            "C:\\Users\\me\\file.txt", "d:/a b/c", "C:\\", "C:\\\\foo",
+            "\\\\server\\share\\dir", "\\\\wsl$\\Ubuntu\\home\\me",
+            "//server/share/dir", "\\\\?\\C:\\long\\path",
+            "--file=C:\\x\\y", ":(exclude)C:\\build", ":!src",
+            "file:///C:/mirror/repo.git", "file:///mnt/c/repo.git",
+            "src", "src/main.rs", ".\\src\\main.rs", "..", ".",
+            "/mnt/c/foo", "/home/me/repo", "/", "~/repo",
+            "HEAD~1", "origin/master", "--version", "-C", "a:b", ""] {
+        let once = translate_path_to_unix(arg).into_owned();
+        assert_eq!(translate_path_to_unix(&once), once, "for {:?}", arg);
+    }
+}
+
+#[test]
+fn win_path_precheck() {
+    // every kind of argument which may be translated passes the check
+    for arg in &["C:\\", "c:/foo", "C:foo", "--file=C:\\x", ":(exclude)C:\\build",
+                 ":!C:/build", "file:///C:/repo.git", "\\\\server\\share",
+                 "//server/share", "\\\\wsl$\\Ubuntu\\home", ".\\src\\main.rs",
+                 "src\\main.rs", "C:\\a.exe C:\\b.txt", "HEAD:src/main.rs"] {
+        assert!(may_contain_win_path(arg), "{}", arg);
+    }
+    for arg in &["src/main.rs", "--cached", "HEAD~1", "/home/me", "-", ""] {
+        assert!(!may_contain_win_path(arg), "{}", arg);
+        assert!(matches!(translate_path_to_unix(arg), Cow::Borrowed(_)));
+    }
+}
+
+#[test]
+fn unix_paths_unchanged() {
+    for &arg in &["/mnt/c/foo", "/home/me/repo", "/", "/tmp/x y"] {
+        assert_eq!(translate_path_to_unix(arg), arg);
+    }
+}
+
+#[test]
+fn unc_path_translation() {
+    assert_eq!(
+        translate_path_to_unix("\\\\server\\share\\dir\\file.txt"),
+        "//server/share/dir/file.txt");
+    assert_eq!(
+        translate_path_to_unix("\\\\wsl$\\Ubuntu\\home\\me"),
+        "/home/me");
+    assert_eq!(
+        translate_path_to_unix("\\\\"),
+        "\\\\");
+}
+
+#[test]
+fn target_distro_detection() {
+    assert!(is_same_distro("ubuntu", Some("Ubuntu")));
+    // e.g. \\wsl$\Debian\home is no path in Ubuntu
+    assert!(!is_same_distro("Debian", Some("Ubuntu")));
+    assert!(is_same_distro("Debian", None));
+}
+
+#[test]
+fn verbatim_path_translation() {
+    assert_eq!(translate_path_to_unix("\\\\?\\C:\\a\\b"), "/mnt/c/a/b");
+    assert_eq!(translate_path_to_unix("--file=\\\\?\\D:\\x"), "--file=/mnt/d/x");
+    assert_eq!(
+        translate_path_to_unix("\\\\?\\UNC\\server\\share\\x"),
+        "//server/share/x");
+    assert_eq!(translate_path_to_unix("\\\\?\\"), "\\\\?\\");
+    // volumes and devices without a drive are kept
+    assert_eq!(translate_path_to_unix("\\\\?\\Volume{1}\\dir"), "\\\\?\\Volume{1}\\dir");
+    assert_eq!(translate_path_to_unix("\\\\.\\COM1"), "\\\\.\\COM1");
+}
+
+#[test]
+fn verbatim_prefix_stripping() {
+    assert_eq!(strip_verbatim_prefix("\\\\?\\C:\\a\\b"), "C:\\a\\b");
+    assert_eq!(strip_verbatim_prefix("\\\\?\\UNC\\server\\share\\x"),
+               "\\\\server\\share\\x");
+    assert_eq!(strip_verbatim_prefix("\\\\?\\unc\\server\\share"), "\\\\server\\share");
+    // nothing, or no drive or server after the prefix
+    assert_eq!(strip_verbatim_prefix("\\\\?\\"), "\\\\?\\");
+    assert_eq!(strip_verbatim_prefix("\\\\?\\Volume{1}\\x"), "\\\\?\\Volume{1}\\x");
+    assert_eq!(strip_verbatim_prefix("C:\\a"), "C:\\a");
+    assert_eq!(strip_verbatim_prefix("\\\\server\\share"), "\\\\server\\share");
+}
+
+#[test]
+fn command_program_translation() {
+    assert_eq!(split_program("\"C:\\Program Files\\t.exe\" --wait"),
+               ("C:\\Program Files\\t.exe", " --wait"));
+    assert_eq!(split_program("ssh -i key"), ("ssh", " -i key"));
+    assert_eq!(split_program("'unterminated"), ("'unterminated", ""));
+    assert_eq!(
+        translate_command_to_unix("\"C:\\Program Files\\Tool\\t.exe\" --wait"),
+        "'/mnt/c/Program Files/Tool/t.exe' --wait");
+    assert_eq!(translate_command_to_unix("C:\\tools\\ssh.exe -i C:\\key"),
+               "/mnt/c/tools/ssh.exe -i C:\\key");
+    assert_eq!(translate_command_to_unix("vim"), "vim");
+    assert_eq!(translate_command_to_unix("code --wait"), "code --wait");
+    assert_eq!(translate_command_to_unix("\"vim\" -f"), "\"vim\" -f");
+}
+
+#[test]
+fn shell_escaping() {
+    assert_eq!(shell_escape("--version".to_owned()), "--version");
+    assert_eq!(shell_escape("/mnt/c/a/b.txt".to_owned()), "/mnt/c/a/b.txt");
+    assert_eq!(shell_escape("".to_owned()), "''");
+    assert_eq!(shell_escape("a space".to_owned()), "'a space'");
+    assert_eq!(shell_escape("cost is $5".to_owned()), "'cost is $5'");
+    assert_eq!(shell_escape("$VAR".to_owned()), "'$VAR'");
+    assert_eq!(shell_escape("`whoami`".to_owned()), "'`whoami`'");
+    assert_eq!(shell_escape("it's".to_owned()), "'it'\\''s'");
+    assert_eq!(shell_escape("say \"hi\"".to_owned()), "'say \"hi\"'");
+    assert_eq!(shell_escape("*.rs".to_owned()), "'*.rs'");
+    assert_eq!(shell_escape("line\nbreak".to_owned()), "'line\nbreak'");
+    // quotes are passed literally, the same as without a shell
+    assert_eq!(shell_escape("\"foo bar\"".to_owned()), "'\"foo bar\"'");
+    assert_eq!(shell_escape("'foo bar'".to_owned()), "''\\''foo bar'\\'''");
+    assert_eq!(shell_escape("foo\" bar".to_owned()), "'foo\" bar'");
+    assert_eq!(shell_escape("\"foo'".to_owned()), "'\"foo'\\'''");
+    assert_eq!(shell_escape("\"".to_owned()), "'\"'");
+}
+
+#[test]
+fn quoted_arg_translation() {
+    // paths enclosed in quotes by the caller
+    assert_eq!(translate_args_to_unix(os_args(&["-C", "'my repo'", "status"])),
+               os_args(&["-C", "my repo", "status"]));
+    assert_eq!(translate_args_to_unix(os_args(&["log", "--", "\"sub dir/a.txt\"", "\"\""])),
+               os_args(&["log", "--", "sub dir/a.txt", ""]));
+    assert_eq!(translate_args_to_unix(os_args(&["worktree", "add", "\"..\\wt\""])),
+               os_args(&["worktree", "add", "../wt"]));
+    // anything else keeps its quotes, as do partially or ambiguously quoted paths
+    for literal_args in &[
+            &["commit", "-m", "\"quoted\""][..],
+            &["grep", "\"x\""],
+            &["grep", "'foo bar'"],
+            &["log", "--", "foo\" bar", "\"$HOME\"", "\"a\" \"b\""]] {
+        assert_eq!(translate_args_to_unix(os_args(literal_args)), os_args(literal_args));
+    }
+}
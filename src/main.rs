@@ -1,94 +1,618 @@
 use std::env;
-use std::process::{Command, Stdio};
-use std::io::{self, Write};
+use std::process::{self, Child, Command, ExitStatus, Stdio};
+use std::fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::thread;
+use std::time::Duration;
 use std::borrow::Cow;
-use std::path::{Path, Component, PrefixComponent, Prefix};
+use std::path::{Path, PathBuf};
+use std::ffi::OsString;
+use std::fmt;
 
-#[macro_use] extern crate lazy_static;
-extern crate regex;
-use regex::bytes::Regex;
+extern crate wslgit;
+use wslgit::{
+    config, console, copy_input, default_distro, find_git_command, find_git_command_index,
+    get_distro,
+    get_mount_root, get_wsl_version, shell_escape, skip_to_marker, translate_args_to_unix,
+    translate_lines, translate_path_list_to_unix, translate_path_to_unix,
+    translate_command_to_unix, translate_path_to_win,
+    translate_grep_lines_capped, translate_records, translate_records_capped, LineEnding, WslVersion, OUTPUT_MARKER};
 
 
-fn get_drive_letter(pc: &PrefixComponent) -> Option<String> {
-    let drive_byte = match pc.kind() {
-        Prefix::VerbatimDisk(d) => Some(d),
-        Prefix::Disk(d) => Some(d),
+fn is_version_or_help(args: &[OsString]) -> bool {
+    // without any arguments, git prints its usage, like with --help
+    const OPTIONS: &[&str] = &["--version", "--help", "-h"];
+    const COMMANDS: &[&str] = &["version", "help"];
+    args.is_empty()
+        || args.iter().any(|arg| OPTIONS.iter().any(|&option| arg == option))
+        || args.first().is_some_and(|cmd| COMMANDS.iter().any(|&c| cmd == c))
+}
+
+fn is_wslgit_version(args: &[OsString]) -> bool {
+    // only as first argument, so that it is never taken from the
+    // arguments of a git command
+    args.first().is_some_and(|arg| arg == "--wslgit-version")
+}
+
+fn is_wslgit_test_connection(args: &[OsString]) -> bool {
+    args.first().is_some_and(|arg| arg == "--wslgit-test-connection")
+}
+
+fn translate_paths_cmd(args: &[OsString]) -> Option<Vec<String>> {
+    // `wslgit-translate <path>...` and `wslgit-untranslate <path>...` print
+    // how the paths are translated, without running anything in WSL
+    let cmd = args.first()?;
+    let paths = args[1..].iter().map(|path| path.to_string_lossy());
+    if cmd == "wslgit-translate" {
+        Some(paths.map(|path| translate_path_to_unix(&path).into_owned()).collect())
+    } else if cmd == "wslgit-untranslate" {
+        Some(paths.map(|path| {
+            String::from_utf8_lossy(&translate_path_to_win(path.as_bytes())).into_owned()
+        }).collect())
+    } else {
+        None
+    }
+}
+
+fn difftool_args(args: &[OsString]) -> Option<(&OsString, &[OsString])> {
+    // `--wslgit-as-difftool <tool> <args>...`, configured in WSL as a
+    // difftool or mergetool command to run a Windows program
+    match args {
+        [flag, tool, tool_args @ ..] if flag == "--wslgit-as-difftool" => Some((tool, tool_args)),
         _ => None
-    };
-    drive_byte.map(|drive_letter| {
-        String::from_utf8(vec![drive_letter])
-            .expect(&format!("Invalid drive letter: {}", drive_letter))
-            .to_lowercase()
+    }
+}
+
+fn translate_tool_args(args: &[OsString]) -> Vec<OsString> {
+    // Windows programs expect backslashes; paths which are only available
+    // in WSL, like temporary files below /tmp, are passed as they are
+    args.iter()
+        .map(|arg| match translate_path_to_win(arg.to_string_lossy().as_bytes()) {
+            Cow::Owned(path) => OsString::from(
+                String::from_utf8_lossy(&path).replace('/', "\\")),
+            Cow::Borrowed(_) => arg.clone()
+        })
+        .collect()
+}
+
+fn describe_distro(distro: &Option<String>, default_distro: Option<String>) -> String {
+    // the default distribution is only looked up if none is configured
+    match (distro, default_distro) {
+        (Some(distro), _) => distro.clone(),
+        (None, Some(default_distro)) => format!("{} (default)", default_distro),
+        (None, None) => "default (none installed or wsl not found)".to_owned()
+    }
+}
+
+fn format_wslgit_version(distro: &str, wsl_version: Option<WslVersion>,
+                         shell_mode: ShellMode, mount_root: &str) -> String {
+    format!("wslgit {}\ndistro: {}\nwsl: {}\nshell: {}\nmount root: {}",
+            env!("CARGO_PKG_VERSION"),
+            distro,
+            wsl_version.map_or("unknown".to_owned(), |v| v.number().to_string()),
+            shell_mode.name(),
+            if mount_root.is_empty() { "/" } else { mount_root })
+}
+
+fn format_connection_test(distro: &str, shell_mode: ShellMode,
+                          result: &Result<String, String>) -> String {
+    match *result {
+        Ok(ref git_version) => format!(
+            "wslgit: connection ok\ndistro: {}\nshell: {}\ngit: {}",
+            distro, shell_mode.name(), git_version),
+        Err(ref diagnostic) => format!(
+            "wslgit: connection failed\ndistro: {}\nshell: {}\nerror: {}",
+            distro, shell_mode.name(), diagnostic)
+    }
+}
+
+fn get_output_separator(args: &[OsString]) -> u8 {
+    // with -z, git separates records in its output by NUL instead of newline
+    if args.iter().any(|arg| arg == "-z" || arg == "--null") {
+        b'\0'
+    } else {
+        b'\n'
+    }
+}
+
+fn debug_enabled() -> bool {
+    config::get().debug.unwrap_or(false)
+}
+
+fn dry_run_enabled() -> bool {
+    config::get().dry_run.unwrap_or(false)
+}
+
+fn json_diagnostics_enabled() -> bool {
+    env::var("WSLGIT_DIAGNOSTICS").is_ok_and(|format| format == "json")
+}
+
+fn merge_stderr_enabled() -> bool {
+    config::get().merge_stderr.unwrap_or(false)
+}
+
+fn format_wsl_cmd(wsl_exe: &str, cmd_args: &[OsString]) -> String {
+    let mut cmd = vec![shell_escape(wsl_exe.to_owned())];
+    cmd.extend(cmd_args.iter()
+        .map(|arg| shell_escape(arg.to_string_lossy().into_owned())));
+    cmd.join(" ")
+}
+
+fn format_debug_info(wsl_exe: &str, cmd_args: &[OsString], distro: &Option<String>,
+                     user: &Option<String>, capture_output: bool) -> String {
+    format!("wslgit: {} (distro: {}, user: {}, output: {})",
+            format_wsl_cmd(wsl_exe, cmd_args),
+            distro.as_ref().map_or("default", |d| d.as_str()),
+            user.as_ref().map_or("default", |u| u.as_str()),
+            if capture_output { "captured and translated" } else { "passed through" })
+}
+
+fn has_message_option(args: &[String]) -> bool {
+    // -m, -F and -C provide the message, also joined with its value or
+    // combined with other short options, like `-am`
+    args.iter().any(|arg| {
+        let argname = arg.split('=').next().unwrap_or("");
+        ["--message", "--file", "--reuse-message"].contains(&argname)
+            || (arg.starts_with('-') && !arg.starts_with("--")
+                && short_options_have_message(&arg[1..]))
     })
 }
 
-fn get_prefix_for_drive(drive: &str) -> String {
-    // todo - lookup mount points
-    format!("/mnt/{}", drive)
+// short options of `commit` and `tag` which take a value, like the key ID
+// in `-S<keyid>` or `-uMyKey`; the rest of the cluster is that value
+const SHORT_OPTIONS_WITH_VALUE: &[char] = &['c', 't', 'S', 'u'];
+
+fn short_options_have_message(cluster: &str) -> bool {
+    for option in cluster.chars() {
+        if ['m', 'F', 'C'].contains(&option) {
+            return true;
+        }
+        if SHORT_OPTIONS_WITH_VALUE.contains(&option) {
+            return false;
+        }
+    }
+    false
+}
+
+fn may_start_editor(args: &[String]) -> bool {
+    // The editor needs the console, so neither its output nor its input
+    // must be redirected by wslgit for these commands.
+    let index = match find_git_command_index(args) {
+        Some(index) => index,
+        None => return false
+    };
+    // options of git itself, like -C, are not options of the command
+    let (command, args) = (args[index].as_str(), &args[index + 1..]);
+    let has_arg = |names: &[&str]| args.iter().any(|arg| names.contains(&arg.as_str()));
+    match command {
+        "commit" | "merge" if has_arg(&["-e", "--edit"]) => true,
+        "commit" => !has_message_option(args) && !has_arg(&["--no-edit"]),
+        "rebase" => has_arg(&["-i", "--interactive"]),
+        "tag" => has_arg(&["-a", "--annotate", "-s", "--sign"])
+            && !has_message_option(args),
+        _ => false
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum TranslateMode {
+    // arguments and output are passed through unchanged
+    Off,
+    // only the arguments are translated
+    In,
+    // only the output is translated
+    Out,
+    Both,
+}
+
+impl TranslateMode {
+    fn translates_input(self) -> bool {
+        self == TranslateMode::In || self == TranslateMode::Both
+    }
+
+    fn translates_output(self) -> bool {
+        self == TranslateMode::Out || self == TranslateMode::Both
+    }
+}
+
+fn parse_translate_mode(mode: &str) -> Option<TranslateMode> {
+    match mode {
+        "off" => Some(TranslateMode::Off),
+        "in" => Some(TranslateMode::In),
+        "out" => Some(TranslateMode::Out),
+        "both" => Some(TranslateMode::Both),
+        _ => None
+    }
+}
+
+fn get_translate_mode() -> TranslateMode {
+    config::get().translate.as_ref()
+        .and_then(|mode| parse_translate_mode(mode))
+        .unwrap_or(TranslateMode::Both)
+}
+
+fn force_translate() -> bool {
+    config::get().force_translate.unwrap_or(false)
 }
 
-fn translate_path_to_unix(argument: String) -> String {
-    {
-        let (argname, arg) = if argument.starts_with("--")
-                             && argument.contains('=') {
-            let parts: Vec<&str> = argument
-                .splitn(2, '=')
-                .collect();
-            (format!("{}=", parts[0]), parts[1])
+// options of rev-parse which print nothing but a path, so that capturing the
+// output takes nothing away on a console; relative paths like `.git` remain
+const PATH_ONLY_OPTIONS: &[&str] = &[
+    "--show-toplevel", "--git-dir", "--git-common-dir", "--absolute-git-dir"];
+
+fn prints_only_paths(args: &[String]) -> bool {
+    find_git_command(args) == Some("rev-parse")
+        && args.iter().any(|arg| PATH_ONLY_OPTIONS.contains(&arg.as_str()))
+}
+
+fn should_capture_output(translate: bool, stdout_is_tty: bool, force: bool) -> bool {
+    // Capturing the output to translate it hides the console from git,
+    // which then disables colors and paging. When a user is looking at the
+    // console, this is worse than seeing some untranslated paths, so the
+    // output is only captured when it is consumed by another program,
+    // unless translation is forced via WSLGIT_FORCE_TRANSLATE.
+    translate && (!stdout_is_tty || force)
+}
+
+// options of git itself, which disable or enable the pager
+const PAGER_OPTIONS: &[&str] = &["-P", "--no-pager", "-p", "--paginate"];
+
+fn sets_pager(args: &[OsString]) -> bool {
+    // only the options before the git command, e.g. not `-p` of `git log -p`
+    let lossy_args: Vec<String> = args.iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let end = find_git_command_index(&lossy_args).unwrap_or(lossy_args.len());
+    lossy_args[..end].iter().any(|arg| PAGER_OPTIONS.contains(&arg.as_str()))
+}
+
+fn build_git_args(args: Vec<OsString>, capture_output: bool) -> Vec<OsString> {
+    let mut git_args = vec![OsString::from("git")];
+    if capture_output && !sets_pager(&args) {
+        // a pager would only see the pipe used to capture the output;
+        // if the pager is disabled already, or explicitly requested,
+        // the options of the user are kept as they are
+        git_args.push(OsString::from("--no-pager"));
+    }
+    git_args.extend(args);
+    git_args
+}
+
+fn get_cwd_unix(cwd: &Path) -> Option<String> {
+    // Network shares are not mounted inside WSL, so there is no directory
+    // to change to. wsl then starts in the home directory instead.
+    let cwd_unix = translate_path_to_unix(&cwd.to_string_lossy()).into_owned();
+    if cwd_unix.starts_with('/') && !cwd_unix.starts_with("//") {
+        Some(cwd_unix)
+    } else {
+        None
+    }
+}
+
+fn cwd_env(cwd_unix: Option<&str>) -> Vec<(String, String)> {
+    // Scripts run by git, like hooks, may rely on PWD instead of the actual
+    // working directory. Without a shell, nothing sets it, and a shell only
+    // keeps PWD if it names its working directory. OLDPWD is the directory
+    // in which WSL started, the same one, rather than the home directory.
+    match cwd_unix {
+        Some(cwd) => vec![("PWD".to_owned(), cwd.to_owned()),
+                          ("OLDPWD".to_owned(), cwd.to_owned())],
+        None => Vec::new()
+    }
+}
+
+fn build_shell_cmd(cwd_unix: Option<String>, git_args: Vec<OsString>,
+                   mark_output: bool, merge_stderr: bool) -> String {
+    // the command is a single argument for bash, so arguments that
+    // are not valid unicode cannot be preserved exactly here
+    let mut git_cmd = git_args.into_iter()
+        .map(|arg| shell_escape(arg.to_string_lossy().into_owned()))
+        .collect::<Vec<String>>()
+        .join(" ");
+    if merge_stderr {
+        // errors are written to stdout, in order with the other output
+        git_cmd.push_str(" 2>&1");
+    }
+    let mut cmds = Vec::new();
+    if let Some(cwd) = cwd_unix {
+        cmds.push(format!("cd {}", shell_escape(cwd)));
+    }
+    if mark_output {
+        // anything printed before the marker comes from the startup files
+        // of the shell and is removed from the captured output
+        cmds.push(format!("echo {}", OUTPUT_MARKER));
+    }
+    cmds.push(git_cmd);
+    // only the `&&` operator must remain visible to the shell
+    cmds.join(" && ")
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ShellMode {
+    // bash -ic, reads .bashrc
+    Interactive,
+    // bash -lc, reads .profile
+    Login,
+    // bash -c, reads only the file in BASH_ENV
+    NonInteractive,
+    // git is executed directly by wsl
+    NoShell,
+}
+
+impl ShellMode {
+    fn name(self) -> &'static str {
+        match self {
+            ShellMode::Interactive => "interactive",
+            ShellMode::Login => "login",
+            ShellMode::NonInteractive => "non-interactive",
+            ShellMode::NoShell => "none"
+        }
+    }
+
+    fn reads_startup_files(self) -> bool {
+        self == ShellMode::Interactive || self == ShellMode::Login
+    }
+
+    fn bash_option(self) -> Option<&'static str> {
+        match self {
+            ShellMode::Interactive => Some("-ic"),
+            ShellMode::Login => Some("-lc"),
+            ShellMode::NonInteractive => Some("-c"),
+            ShellMode::NoShell => None
+        }
+    }
+}
+
+fn parse_shell_mode(mode: &str) -> Option<ShellMode> {
+    match mode {
+        "interactive" => Some(ShellMode::Interactive),
+        "login" => Some(ShellMode::Login),
+        "none" => Some(ShellMode::NoShell),
+        _ => None
+    }
+}
+
+fn get_shell_mode() -> ShellMode {
+    config::get().shell.as_ref()
+        .and_then(|mode| parse_shell_mode(mode))
+        .unwrap_or_else(|| if use_interactive_shell() {
+            ShellMode::Interactive
         } else {
-            ("".to_owned(), argument.as_ref())
+            ShellMode::NonInteractive
+        })
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum StdinMode {
+    // git reads from the stdin of wslgit
+    Inherit,
+    // wslgit copies its stdin to git
+    Pipe,
+    // like Pipe, but CRLF line endings of messages are converted to LF
+    Lf,
+}
+
+fn parse_stdin_mode(mode: &str) -> Option<StdinMode> {
+    match mode {
+        "inherit" => Some(StdinMode::Inherit),
+        "pipe" => Some(StdinMode::Pipe),
+        "lf" => Some(StdinMode::Lf),
+        _ => None
+    }
+}
+
+fn get_stdin_mode() -> StdinMode {
+    config::get().stdin.as_ref()
+        .and_then(|mode| parse_stdin_mode(mode))
+        .unwrap_or(StdinMode::Inherit)
+}
+
+fn get_output_eol() -> LineEnding {
+    config::get().output_eol.as_ref()
+        .and_then(|eol| LineEnding::parse(eol))
+        .unwrap_or(LineEnding::Keep)
+}
+
+// the git command and its options; arguments after `--` are paths, even `-`
+fn git_command_options(args: &[String]) -> Option<(&str, &[String])> {
+    let index = find_git_command_index(args)?;
+    let options = &args[index + 1..];
+    let end = options.iter().position(|arg| arg == "--").unwrap_or(options.len());
+    Some((args[index].as_str(), &options[..end]))
+}
+
+// git commands which read messages from stdin, e.g. with `-F -`
+const TEXT_STDIN_CMDS: &[&str] = &["commit", "tag", "notes", "merge"];
+
+fn reads_text_from_stdin(args: &[String]) -> bool {
+    git_command_options(args).is_some_and(|(command, options)| {
+        TEXT_STDIN_CMDS.contains(&command) && (
+            options.windows(2).any(|pair| (pair[0] == "-F" || pair[0] == "--file") && pair[1] == "-")
+                || options.iter().any(|arg| arg == "-F-" || arg == "--file=-"))
+    })
+}
+
+// git commands which read their input from stdin without further options
+const STDIN_CMDS: &[&str] = &[
+    "am", "apply", "credential", "fast-import", "mktag", "mktree", "patch-id",
+    "stripspace",
+];
+
+fn reads_from_stdin(args: &[String]) -> bool {
+    // a bare `-` is not checked: it names the previous branch for `checkout`
+    reads_text_from_stdin(args) || git_command_options(args).is_some_and(|(command, options)| {
+        STDIN_CMDS.contains(&command) || options.iter().any(|arg| {
+            arg == "--stdin" || arg.starts_with("--stdin-") || arg.starts_with("--batch")
+        })
+    })
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum StdinUse {
+    // git gets no stdin at all
+    Null,
+    // git reads from the stdin of wslgit
+    Inherit,
+    // wslgit copies its stdin to git, converting CRLF to LF if set
+    Pipe { crlf_to_lf: bool },
+}
+
+fn classify_stdin(args: &[OsString], lossy_args: &[String], configured: StdinMode,
+                  start_editor: bool) -> StdinUse {
+    if is_version_or_help(args) {
+        // For some reason, the git subprocess seems to hang, waiting for
+        // input, when VS Code 1.17.2 tries to detect if `git --version` works
+        // on Windows 10 1709 (specifically, in `findSpecificGit` in the
+        // VS Code source file `extensions/git/src/git.ts`).
+        // To workaround this, we don't pass stdin to the git subprocess
+        // for the initial `--version` check, nor for `--help`, which has
+        // been seen to hang the same way.
+        StdinUse::Null
+    } else if start_editor || configured == StdinMode::Inherit || !reads_from_stdin(lossy_args) {
+        // the editor needs the console, and commands which don't read
+        // input, e.g. `log`, may still ask for credentials
+        StdinUse::Inherit
+    } else {
+        StdinUse::Pipe {
+            crlf_to_lf: configured == StdinMode::Lf && reads_text_from_stdin(lossy_args),
+        }
+    }
+}
+
+fn forward_stdin(git_proc: &mut Child, crlf_to_lf: bool, codepage: Option<u32>) {
+    if let Some(mut git_stdin) = git_proc.stdin.take() {
+        // Not joined, as reading from a console blocks until input is
+        // entered, even if git does not read anything. Errors, e.g. when
+        // git exits before all input is copied, are ignored.
+        thread::spawn(move || {
+            let decode = |line: &[u8]| {
+                codepage.and_then(|codepage| console::decode_codepage(line, codepage))
+            };
+            let _ = copy_input(io::stdin().lock(), &mut git_stdin, crlf_to_lf, decode);
+        });
+    }
+}
+
+fn join_lossy(args: &[OsString]) -> String {
+    args.iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<Cow<str>>>()
+        .join(" ")
+}
+
+fn build_cmd_args(wsl_args: Vec<String>, cwd_unix: Option<String>,
+                  git_args: Vec<OsString>, shell_mode: ShellMode,
+                  mark_output: bool, merge_stderr: bool)
+                  -> (Vec<OsString>, String) {
+    // returns all arguments for wsl, and the git command for error messages
+    let mut cmd_args: Vec<OsString> = wsl_args.into_iter()
+        .map(OsString::from).collect();
+    let git_cmd: String;
+    match shell_mode.bash_option() {
+        Some(option) => {
+            // Without --exec, wsl passes the command line to the default
+            // shell, which would interpret quotes, `$` and globs before
+            // bash even sees the quoted command.
+            cmd_args.push(OsString::from("--exec"));
+            cmd_args.push(OsString::from("bash"));
+            cmd_args.push(OsString::from(option));
+            git_cmd = build_shell_cmd(cwd_unix, git_args, mark_output, merge_stderr);
+            cmd_args.push(OsString::from(git_cmd.clone()));
+        }
+        None => {
+            // without a shell, wsl itself has to change the directory
+            if let Some(cwd) = cwd_unix {
+                cmd_args.push(OsString::from("--cd"));
+                cmd_args.push(OsString::from(cwd));
+            }
+            cmd_args.push(OsString::from("--exec"));
+            git_cmd = join_lossy(&git_args);
+            cmd_args.extend(git_args);
+        }
+    }
+    (cmd_args, git_cmd)
+}
+
+// Windows allows 32767 characters, leave some room for the program name
+const MAX_CMD_LINE_LEN: usize = 32000;
+
+fn exceeds_cmd_line_limit(cmd_args: &[OsString]) -> bool {
+    // every argument may need quotes and is separated by a space
+    let len: usize = cmd_args.iter().map(|arg| arg.len() + 3).sum();
+    len > MAX_CMD_LINE_LEN
+}
+
+fn build_script_cmd_args(wsl_args: Vec<String>, shell_mode: ShellMode,
+                         script_unix: String) -> Vec<OsString> {
+    let mut cmd_args: Vec<OsString> = wsl_args.into_iter()
+        .map(OsString::from).collect();
+    cmd_args.push(OsString::from("--exec"));
+    cmd_args.push(OsString::from("bash"));
+    cmd_args.push(OsString::from(shell_mode.bash_option().unwrap_or("-c")));
+    cmd_args.push(OsString::from(format!(". {}", shell_escape(script_unix))));
+    cmd_args
+}
+
+fn translate_response_file(arg: &str, translated_path: &Path) -> io::Result<Option<OsString>> {
+    // `@<file>` passes arguments in a file, one per line, which git in WSL
+    // reads itself, so the paths in it are translated into another file
+    let path = match arg.strip_prefix('@') {
+        Some(path) if Path::new(path).is_file() => path,
+        _ => return Ok(None)
+    };
+    let translated: String = fs::read_to_string(path)?.lines()
+        .map(|line| translate_path_to_unix(line).into_owned() + "\n")
+        .collect();
+    fs::write(translated_path, translated)?;
+    Ok(Some(OsString::from(format!(
+        "@{}", translate_path_to_unix(&translated_path.to_string_lossy())))))
+}
+
+fn translate_response_files(args: Vec<OsString>, temp_dir: &Path)
+        -> Result<(Vec<OsString>, Vec<PathBuf>), WslgitError> {
+    // returns the arguments and the translated files to remove afterwards
+    let mut translated_files = Vec::new();
+    let mut translated_args = Vec::with_capacity(args.len());
+    for arg in args {
+        let translated_path = temp_dir.join(
+            format!("wslgit-{}-{}.args", process::id(), translated_files.len()));
+        let translated = match arg.to_str() {
+            Some(arg) => translate_response_file(arg, &translated_path)
+                .map_err(WslgitError::ResponseFile)?,
+            None => None
         };
-        let win_path = Path::new(arg);
-        if win_path.is_absolute() || win_path.exists() {
-            let wsl_path: String = win_path.components().fold(
-                String::new(), |mut acc, c| {
-                    match c {
-                        Component::Prefix(prefix_comp) => {
-                            let d = get_drive_letter(&prefix_comp).expect(
-                                &format!("Cannot handle path {:?}",
-                                         win_path));
-                            acc.push_str(&get_prefix_for_drive(&d));
-                        }
-                        Component::RootDir => {},
-                        _ => {
-                            let d = c.as_os_str().to_str()
-                                .expect(
-                                    &format!("Cannot represent path {:?}",
-                                             win_path))
-                                .to_owned();
-                            if !acc.is_empty() && !acc.ends_with('/') {
-                                acc.push('/');
-                            }
-                            acc.push_str(&d);
-                        }
-                    };
-                    acc
-                });
-            return format!("{}{}", &argname, &wsl_path);
-        }
-    }
-    argument
-}
-
-fn translate_path_to_win(line: &[u8]) -> Cow<[u8]> {
-    lazy_static! {
-        static ref WSLPATH_RE: Regex =
-            Regex::new(r"(?m-u)/mnt/(?P<drive>[A-Za-z])(?P<path>/\S*)")
-                .expect("Failed to compile WSLPATH regex");
-    }
-    WSLPATH_RE.replace_all(line, &b"${drive}:${path}"[..])
-}
-
-fn shell_escape(arg: String) -> String {
-    // ToDo: This really only handles arguments with spaces and newlines.
-    // More complete shell escaping is required for the general case.
-    if arg.contains(" ") {
-        return vec![
-            String::from("\""),
-            arg,
-            String::from("\"")].join("");
-    }
-    arg.replace("\n", "$'\n'")
+        match translated {
+            Some(translated) => {
+                translated_args.push(translated);
+                translated_files.push(translated_path);
+            }
+            None => translated_args.push(arg)
+        }
+    }
+    Ok((translated_args, translated_files))
+}
+
+fn translate_input_args(args: Vec<OsString>, translate_mode: TranslateMode, temp_dir: &Path)
+        -> Result<(Vec<OsString>, Vec<PathBuf>), WslgitError> {
+    // with WSLGIT_TRANSLATE=off or out, the arguments and response files
+    // are passed on exactly as they are
+    if !translate_mode.translates_input() {
+        return Ok((args, Vec::new()));
+    }
+    let (args, response_files) = translate_response_files(args, temp_dir)?;
+    Ok((translate_args_to_unix(args), response_files))
+}
+
+// wsl.exe is found on PATH, unless another launcher is configured, which
+// must accept the same arguments
+const DEFAULT_WSL_EXE: &str = "wsl";
+
+fn get_wsl_exe(configured: Option<&str>) -> Result<String, WslgitError> {
+    match configured {
+        Some(wsl_exe) if Path::new(wsl_exe).is_file() => Ok(wsl_exe.to_owned()),
+        Some(wsl_exe) => Err(WslgitError::WslExeNotFound(wsl_exe.to_owned())),
+        None => Ok(DEFAULT_WSL_EXE.to_owned())
+    }
 }
 
 fn use_interactive_shell() -> bool {
@@ -109,127 +633,1720 @@ fn use_interactive_shell() -> bool {
     true
 }
 
+fn get_user() -> Option<String> {
+    config::get().user.clone()
+}
 
-fn main() {
-    let mut cmd_args = Vec::new();
-    let cwd_unix = translate_path_to_unix(env::current_dir().unwrap().to_string_lossy().into_owned());
-    let mut git_args: Vec<String> = vec![String::from("cd"), cwd_unix, String::from("&&"), String::from("git")];
-    let git_cmd: String;
+fn get_extra_wsl_args() -> Vec<String> {
+    config::get().wsl_args.clone().unwrap_or_default()
+}
 
-    // process git command arguments
-    git_args.extend(env::args().skip(1)
-        .map(translate_path_to_unix));
+fn wsl_args(distro: Option<String>, user: Option<String>, extra: &[String]) -> Vec<String> {
+    // options for wsl itself, these must precede the command to run
+    let mut args = Vec::new();
+    if let Some(distro) = distro {
+        args.push("-d".to_string());
+        args.push(distro);
+    }
+    if let Some(user) = user {
+        args.push("-u".to_string());
+        args.push(user);
+    }
+    // any other options of wsl, from WSLGIT_WSL_ARGS
+    args.extend(extra.iter().cloned());
+    args
+}
+
+// environment variables of git that contain a single path
+const GIT_PATH_VARS: &[&str] = &[
+    "GIT_DIR", "GIT_WORK_TREE", "GIT_INDEX_FILE",
+    "GIT_OBJECT_DIRECTORY", "GIT_COMMON_DIR"];
+
+// environment variables of git that contain a list of paths
+const GIT_PATH_LIST_VARS: &[&str] = &["GIT_ALTERNATE_OBJECT_DIRECTORIES"];
+
+// environment variables of git that contain a command line, which may
+// start with the path of a Windows program
+const GIT_CMD_VARS: &[&str] = &["GIT_EDITOR", "GIT_SSH_COMMAND"];
+
+fn translate_git_env<F>(lookup: F) -> Vec<(String, String)>
+    where F: Fn(&str) -> Option<String> {
+    let paths = GIT_PATH_VARS.iter()
+        .filter_map(|&name| lookup(name).map(|value| {
+            (name.to_owned(), translate_path_to_unix(&value).into_owned())
+        }));
+    let lists = GIT_PATH_LIST_VARS.iter()
+        .filter_map(|&name| lookup(name).map(|value| {
+            (name.to_owned(), translate_path_list_to_unix(&value))
+        }));
+    let cmds = GIT_CMD_VARS.iter()
+        .filter_map(|&name| lookup(name).map(|value| {
+            (name.to_owned(), translate_command_to_unix(&value).into_owned())
+        }));
+    paths.chain(lists).chain(cmds).collect()
+}
+
+fn pager_env(pager: Option<&str>) -> Vec<(String, String)> {
+    // GIT_PAGER takes precedence over core.pager, so it is only set when
+    // a pager is configured for wslgit; `cat` is how git disables paging
+    match pager {
+        Some("none") | Some("cat") => vec![("GIT_PAGER".to_owned(), "cat".to_owned())],
+        Some(pager) => vec![("GIT_PAGER".to_owned(), pager.to_owned())],
+        None => Vec::new()
+    }
+}
+
+fn parse_wslenv(wslenv: &str) -> Vec<(&str, &str)> {
+    // VAR1/flags:VAR2/flags into pairs of name and flags
+    wslenv.split(':')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let mut parts = entry.splitn(2, '/');
+            (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+        })
+        .collect()
+}
+
+fn translate_wslenv_vars<F>(wslenv: &str, lookup: F) -> Vec<(String, String)>
+    where F: Fn(&str) -> Option<String> {
+    // Translate the variables with the flags /p (a path) or /l (a list of
+    // paths) like wsl does, but with the same translation as arguments.
+    // Variables only passed from WSL to Windows (/w) are left alone.
+    parse_wslenv(wslenv).into_iter()
+        .filter(|&(name, flags)| {
+            !GIT_PATH_VARS.contains(&name) && !GIT_PATH_LIST_VARS.contains(&name)
+                && !GIT_CMD_VARS.contains(&name)
+                && (flags.contains('p') || flags.contains('l'))
+                && (!flags.contains('w') || flags.contains('u'))
+        })
+        .filter_map(|(name, flags)| lookup(name).map(|value| {
+            let value = if flags.contains('l') {
+                translate_path_list_to_unix(&value)
+            } else {
+                translate_path_to_unix(&value).into_owned()
+            };
+            (name.to_owned(), value)
+        }))
+        .collect()
+}
+
+fn build_wslenv(wslenv: &str, names: &[&str]) -> String {
+    // The values are translated already, so any flags in WSLENV for
+    // these variables are dropped, as /p would translate them again.
+    let mut entries: Vec<&str> = wslenv.split(':')
+        .filter(|entry| {
+            let name = entry.split('/').next().unwrap_or("");
+            !entry.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name))
+        })
+        .collect();
+    entries.extend(names);
+    entries.join(":")
+}
+
+// add git commands that must use translate_path_to_win, also for the paths
+// after a prefix, like `Would remove /mnt/c/x` of `git clean -n`
+const TRANSLATED_CMDS: &[&str] = &[
+    "rev-parse", "remote", "clean", "status", "format-patch", "worktree", "submodule",
+    "apply", "am", "ls-tree"];
+
+// git commands whose output lines start with a path, followed by the content
+// of a file, in which nothing is translated
+const LEADING_PATH_CMDS: &[&str] = &["grep"];
+
+fn prints_leading_paths(args: &[String]) -> bool {
+    find_git_command(args).is_some_and(|cmd| LEADING_PATH_CMDS.contains(&cmd))
+}
+
+// options which make other git commands print paths, e.g. the files
+// of `git config --list --show-origin`, as in `file:/mnt/c/.gitconfig`,
+// or those of `git log --stat`, also given as e.g. `--stat=80`; only the
+// fields of `git cat-file --batch-check`, not the objects of `--batch`
+const TRANSLATED_OPTIONS: &[(&str, &str)] = &[
+    ("config", "--show-origin"), ("log", "--stat"), ("cat-file", "--batch-check")];
 
-    if use_interactive_shell() {
-        cmd_args.push("bash".to_string());
-        cmd_args.push("-ic".to_string());
-        git_cmd = git_args.into_iter().map(shell_escape).collect::<Vec<String>>().join(" ");
-        cmd_args.push(git_cmd.clone());
+// options which make translated git commands print other content instead,
+// e.g. the patches themselves with `git format-patch --stdout`, or ask
+// questions, which must not wait for the end of their line
+const UNTRANSLATED_OPTIONS: &[(&str, &str)] = &[
+    ("format-patch", "--stdout"), ("am", "-i"), ("am", "--interactive")];
+
+// read-only git commands which IDEs run constantly, e.g. to update the
+// state of a repository, and which do not need the setup of the shell
+const DEFAULT_DIRECT_CMDS: &[&str] = &["rev-parse", "status", "symbolic-ref"];
+
+// With WSL 1, which translates the Linux syscalls, starting the shell and
+// the processes of its startup files is much slower, so that also the other
+// read-only commands IDEs run often are run without it.
+const WSL1_DIRECT_CMDS: &[&str] = &[
+    "log", "show", "diff", "ls-files", "cat-file", "for-each-ref"];
+
+fn default_direct_cmds(wsl_version: Option<WslVersion>) -> Vec<String> {
+    let wsl1_cmds = if wsl_version == Some(WslVersion::Wsl1) { WSL1_DIRECT_CMDS } else { &[] };
+    DEFAULT_DIRECT_CMDS.iter().chain(wsl1_cmds).map(|&cmd| cmd.to_owned()).collect()
+}
+
+fn get_direct_cmds() -> Vec<String> {
+    config::get().direct_cmds.clone()
+        .unwrap_or_else(|| default_direct_cmds(get_wsl_version()))
+}
+
+fn uses_direct_path(args: &[String], direct_cmds: &[String], shell_mode: ShellMode,
+                    merge_stderr: bool) -> bool {
+    // git must then be found on PATH without .bashrc or .profile; a login
+    // shell is used precisely when PATH is only set up in .profile
+    shell_mode != ShellMode::NoShell && shell_mode != ShellMode::Login && !merge_stderr
+        && find_git_command(args).is_some_and(|cmd| direct_cmds.iter().any(|c| c == cmd))
+}
+
+fn spawn_with_fallback<T, F>(shell_mode: ShellMode, fallback_mode: Option<ShellMode>,
+                             mut spawn: F) -> Result<T, WslgitError>
+        where F: FnMut(ShellMode) -> Result<T, WslgitError> {
+    match (spawn(shell_mode), fallback_mode) {
+        (Err(_), Some(fallback_mode)) => spawn(fallback_mode),
+        (result, _) => result
     }
-    else {
-        git_cmd = git_args.join(" ");
-        cmd_args = git_args;
+}
+
+fn is_exec_failure(line: &[u8]) -> bool {
+    // the error of wsl when the program of `--exec` is not found, e.g.
+    // `execvpe(git) failed: No such file or directory`, or in newer versions
+    // `<3>WSL (8) ERROR: CreateProcessEntryCommon:370: execvpe git failed 2`
+    let line = String::from_utf8_lossy(line);
+    line.contains("execvpe") && line.contains("failed")
+}
+
+fn falls_back_to_shell(exec_failure: bool, exit_code: Option<i32>, input_reusable: bool)
+        -> bool {
+    // git was not found on the PATH without the startup files of the shell,
+    // so it never ran, but only if its input was not consumed already
+    exec_failure && exit_code != Some(0) && input_reusable
+}
+
+fn copy_stderr<R: BufRead, W: Write>(mut input: R, out: &mut W, translate: bool)
+        -> io::Result<()> {
+    if translate {
+        return translate_lines(input, out);
     }
+    io::copy(&mut input, out)?;
+    out.flush()
+}
 
-    // setup stdin/stdout
-    let stdin_mode = if env::args().last().unwrap() == "--version" {
-        // For some reason, the git subprocess seems to hang, waiting for
-        // input, when VS Code 1.17.2 tries to detect if `git --version` works
-        // on Windows 10 1709 (specifically, in `findSpecificGit` in the
-        // VS Code source file `extensions/git/src/git.ts`).
-        // To workaround this, we only pass stdin to the git subprocess
-        // for all other commands, but not for the initial `--version` check.
-        // Stdin is needed for example when commiting, where the commit
-        // message is passed on stdin.
-        Stdio::null()
-    } else {
-        Stdio::inherit()
-    };
-
-    // setup the git subprocess launched inside WSL
-    let mut git_proc_setup = Command::new("wsl");
-    git_proc_setup.args(&cmd_args)
-        .stdin(stdin_mode);
-    let status;
-
-    // add git commands that must use translate_path_to_win
-    const TRANSLATED_CMDS: &[&str] = &["rev-parse", "remote"];
-
-    let translate_output =
-       env::args().skip(1).position(|arg| TRANSLATED_CMDS.iter().position(|&tcmd| tcmd == arg).is_some()).is_some();
-
-    if translate_output {
-        // run the subprocess and capture its output
-        let git_proc = git_proc_setup.stdout(Stdio::piped())
-            .spawn()
-            .expect(&format!("Failed to execute command '{}'", &git_cmd));
-        let output = git_proc
-            .wait_with_output()
-            .expect(&format!("Failed to wait for git call '{}'", &git_cmd));
-        status = output.status;
-        let output_bytes = output.stdout;
-        let mut stdout = io::stdout();
-        stdout
-            .write_all(&translate_path_to_win(&output_bytes))
-            .expect("Failed to write git output");
-        stdout.flush().expect("Failed to flush output");
-    }
-    else {
-        // run the subprocess without capturing its output
-        // the output of the subprocess is passed through unchanged
-        status = git_proc_setup.status()
-            .expect(&format!("Failed to execute command '{}'", &git_cmd));
+fn forward_stderr<R: BufRead, W: Write>(mut input: R, out: &mut W, translate: bool,
+                                        hold_exec_failure: bool)
+        -> io::Result<Option<Vec<u8>>> {
+    // With the direct path, the first line is checked before anything is
+    // written. If it is the error of wsl that git was not found, it is
+    // returned with the rest instead, as git is then run in the shell.
+    if !hold_exec_failure {
+        copy_stderr(input, out, translate)?;
+        return Ok(None);
     }
+    let mut first_line = Vec::new();
+    input.read_until(b'\n', &mut first_line)?;
+    if is_exec_failure(&first_line) {
+        input.read_to_end(&mut first_line)?;
+        return Ok(Some(first_line));
+    }
+    copy_stderr((&first_line[..]).chain(input), out, translate)?;
+    Ok(None)
+}
 
-    // forward any exit code
-    if let Some(exit_code) = status.code() {
-        std::process::exit(exit_code);
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum StartFailure {
+    // wsl could not be started at all
+    Spawn(io::ErrorKind),
+    // wsl exited with this code, without a code after a signal
+    Exit(Option<i32>),
+}
+
+const DEFAULT_START_RETRIES: u32 = 1;
+const START_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+fn get_start_retries() -> u32 {
+    config::get().start_retries.unwrap_or(DEFAULT_START_RETRIES)
+}
+
+fn is_transient_start_failure(failure: StartFailure) -> bool {
+    // Exit codes of git are never negative, but those of wsl.exe itself
+    // are, e.g. when the WSL instance terminated while it was started.
+    match failure {
+        StartFailure::Spawn(kind) => kind != io::ErrorKind::NotFound,
+        StartFailure::Exit(code) => code.is_some_and(|code| code < 0)
+    }
+}
+
+fn needs_fallback_git(failure: StartFailure) -> bool {
+    // Only if WSL itself is unavailable, i.e. wsl.exe is missing or fails
+    // with its own negative exit code, e.g. without WSL enabled, and never
+    // after git ran and failed.
+    match failure {
+        StartFailure::Spawn(kind) => kind == io::ErrorKind::NotFound,
+        StartFailure::Exit(code) => code.is_some_and(|code| code < 0)
     }
 }
 
+fn fallback_git_command(fallback_git: &str, args: &[OsString]) -> Command {
+    // a native git gets the arguments as given, without any translation
+    let mut command = Command::new(fallback_git);
+    command.args(args);
+    command
+}
 
-#[test]
-fn win_to_unix_path_trans() {
-    assert_eq!(
-        translate_path_to_unix("d:\\test\\file.txt".to_string()),
-        "/mnt/d/test/file.txt");
-    assert_eq!(
-        translate_path_to_unix("C:\\Users\\test\\a space.txt".to_string()),
-        "/mnt/c/Users/test/a space.txt");
+fn run_fallback_git(fallback_git: &str, args: &[OsString]) -> Result<ExitStatus, WslgitError> {
+    if debug_enabled() {
+        eprintln!("wslgit: WSL is not available, running {}", fallback_git);
+    }
+    fallback_git_command(fallback_git, args).status()
+        .map_err(|err| WslgitError::Execute(fallback_git.to_owned(), err))
 }
 
-#[test]
-fn unix_to_win_path_trans() {
-    assert_eq!(
-        &*translate_path_to_win(b"/mnt/d/some path/a file.md"),
-        b"d:/some path/a file.md");
-    assert_eq!(
-        &*translate_path_to_win(b"origin  /mnt/c/path/ (fetch)"),
-        b"origin  c:/path/ (fetch)");
-    let multiline = b"mirror  /mnt/c/other/ (fetch)\nmirror  /mnt/c/other/ (push)\n";
-    let multiline_result = b"mirror  c:/other/ (fetch)\nmirror  c:/other/ (push)\n";
-    assert_eq!(
-        &*translate_path_to_win(&multiline[..]),
-        &multiline_result[..]);
+fn should_retry_start<F>(failure: StartFailure, attempt: u32, retries: u32,
+                         is_transient: F) -> bool
+        where F: Fn(StartFailure) -> bool {
+    attempt < retries && is_transient(failure)
 }
 
-#[test]
-fn no_path_translation() {
-    assert_eq!(
-        &*translate_path_to_win(b"/mnt/other/file.sh"),
-        b"/mnt/other/file.sh");
+// git commands whose output is binary, e.g. with `-o -`, and must never
+// be translated, even if forced or listed as translated command
+const BINARY_OUTPUT_CMDS: &[&str] = &["archive", "bundle"];
+
+fn get_no_translate_cmds() -> Vec<String> {
+    config::get().no_translate.clone().unwrap_or_default()
 }
 
-#[test]
-fn relative_path_translation() {
-    assert_eq!(
-        translate_path_to_unix(".\\src\\main.rs".to_string()),
-        "./src/main.rs");
+fn should_translate_output(args: &[String], no_translate: &[String]) -> bool {
+    // Only the git command itself is matched, which follows the options of
+    // git, like `-c key=value`, so that neither their values nor other
+    // arguments, like a branch named `remote`, decide about the output.
+    // Commands listed in WSLGIT_NO_TRANSLATE take precedence over the
+    // built-in list of translated commands.
+    let command = match find_git_command(args) {
+        Some(command) => command,
+        None => return false
+    };
+    let has_arg = |name: &str| args.iter().any(|arg| {
+        arg.strip_prefix(name).is_some_and(|value| value.is_empty() || value.starts_with('='))
+    });
+    (TRANSLATED_CMDS.contains(&command)
+     || prints_leading_paths(args)
+     || TRANSLATED_OPTIONS.iter().any(|&(cmd, option)| command == cmd && has_arg(option)))
+        && !UNTRANSLATED_OPTIONS.iter().any(|&(cmd, option)| command == cmd && has_arg(option))
+        && !no_translate.iter().any(|cmd| cmd == command)
+        && !BINARY_OUTPUT_CMDS.contains(&command)
 }
 
-#[test]
-fn long_argument_path_translation() {
-    assert_eq!(
-        translate_path_to_unix("--file=C:\\some\\path.txt".to_owned()),
-        "--file=/mnt/c/some/path.txt");
+
+#[derive(Debug)]
+enum WslgitError {
+    CurrentDir(io::Error),
+    ScriptFile(io::Error),
+    ResponseFile(io::Error),
+    WslNotFound,
+    WslExeNotFound(String),
+    Execute(String, io::Error),
+    Wait(String, io::Error),
+    Output(io::Error),
+}
+
+impl WslgitError {
+    fn from_spawn_error(err: io::Error, git_cmd: &str) -> WslgitError {
+        match err.kind() {
+            io::ErrorKind::NotFound => WslgitError::WslNotFound,
+            _ => WslgitError::Execute(git_cmd.to_owned(), err)
+        }
+    }
+}
+
+impl WslgitError {
+    // stable names for tools, unlike the messages
+    fn kind(&self) -> &'static str {
+        match *self {
+            WslgitError::CurrentDir(_) => "current_dir",
+            WslgitError::ScriptFile(_) => "script_file",
+            WslgitError::ResponseFile(_) => "response_file",
+            WslgitError::WslNotFound => "wsl_not_found",
+            WslgitError::WslExeNotFound(_) => "wsl_exe_not_found",
+            WslgitError::Execute(..) => "execute",
+            WslgitError::Wait(..) => "wait",
+            WslgitError::Output(_) => "output",
+        }
+    }
+
+    fn spawn_failure(&self) -> Option<StartFailure> {
+        match *self {
+            WslgitError::WslNotFound => Some(StartFailure::Spawn(io::ErrorKind::NotFound)),
+            WslgitError::Execute(_, ref err) => Some(StartFailure::Spawn(err.kind())),
+            _ => None
+        }
+    }
+
+    fn git_cmd(&self) -> Option<&str> {
+        match *self {
+            WslgitError::Execute(ref git_cmd, _) | WslgitError::Wait(ref git_cmd, _) =>
+                Some(git_cmd),
+            _ => None
+        }
+    }
+}
+
+fn distro_hint<F>(err: &WslgitError, distro: &Option<String>, default_distro: F)
+    -> Option<String> where F: FnOnce() -> Option<String> {
+    // wsl itself could be run, so name the distribution it used
+    match (err, distro) {
+        (&WslgitError::Execute(..), &None) => Some(match default_distro() {
+            Some(default_distro) => format!("using the default distribution '{}'",
+                                            default_distro),
+            None => "no WSL distribution is installed, install one or set WSLGIT_DISTRO"
+                .to_owned()
+        }),
+        _ => None
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c)
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn format_json_diagnostic(err: &WslgitError, distro: Option<&str>, args: &[OsString]) -> String {
+    // one line with the error, for tools which run wslgit, e.g. IDEs;
+    // the command is the one run in WSL, if it was started already
+    let command = match err.git_cmd() {
+        Some(git_cmd) => git_cmd.to_owned(),
+        None => format!("git {}", join_lossy(args)).trim_end().to_owned()
+    };
+    format!("{{\"error\":{},\"kind\":{},\"distro\":{},\"command\":{}}}",
+            json_string(&err.to_string()), json_string(err.kind()),
+            distro.map_or_else(|| "null".to_owned(), json_string),
+            json_string(&command))
+}
+
+impl fmt::Display for WslgitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WslgitError::CurrentDir(ref err) =>
+                write!(f, "Failed to get the current directory: {}", err),
+            WslgitError::ScriptFile(ref err) =>
+                write!(f, "Failed to write script for long command: {}", err),
+            WslgitError::ResponseFile(ref err) =>
+                write!(f, "Failed to translate response file: {}", err),
+            WslgitError::WslNotFound =>
+                write!(f, "WSL (wsl.exe) not found on PATH"),
+            WslgitError::WslExeNotFound(ref wsl_exe) =>
+                write!(f, "WSL launcher '{}' from WSLGIT_WSL_EXE not found", wsl_exe),
+            WslgitError::Execute(ref git_cmd, ref err) =>
+                write!(f, "Failed to execute command '{}': {}", git_cmd, err),
+            WslgitError::Wait(ref git_cmd, ref err) =>
+                write!(f, "Failed to wait for git call '{}': {}", git_cmd, err),
+            WslgitError::Output(ref err) =>
+                write!(f, "Failed to write git output: {}", err),
+        }
+    }
+}
+
+fn status_to_exit_code(status: ExitStatus) -> i32 {
+    // Without an exit code, the process was terminated by a signal.
+    // Follow the shell convention of 128 + signal number for this case,
+    // but never report success.
+    status.code().unwrap_or_else(|| signal_exit_code(status))
+}
+
+#[cfg(unix)]
+fn signal_exit_code(status: ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().map_or(1, |signal| 128 + signal)
+}
+
+#[cfg(not(unix))]
+fn signal_exit_code(_status: ExitStatus) -> i32 {
+    1
+}
+
+
+fn get_max_capture() -> usize {
+    // unlimited, unless set to a number of bytes
+    config::get().max_capture.unwrap_or(usize::MAX)
+}
+
+fn translate_output(git_proc: &mut Child, mark_output: bool, leading_paths: bool,
+                    output_separator: u8, eol: LineEnding, max_capture: usize)
+        -> io::Result<()> {
+    // stderr is forwarded in a separate thread, see forward_git_stderr
+    let mut git_stdout = BufReader::new(git_proc.stdout.take()
+        .expect("Failed to capture git output"));
+    if mark_output {
+        let unmarked = skip_to_marker(&mut git_stdout, OUTPUT_MARKER)?;
+        // without marker, git did not even run, so nothing is removed
+        translate_records(&unmarked[..], &mut io::stdout(), output_separator, eol)?;
+    }
+    let passed_through = if leading_paths {
+        translate_grep_lines_capped(git_stdout, &mut io::stdout(), eol, max_capture)?
+    } else {
+        translate_records_capped(
+            git_stdout, &mut io::stdout(), output_separator, eol, max_capture)?
+    };
+    if passed_through {
+        eprintln!("wslgit: output longer than WSLGIT_MAX_CAPTURE ({} bytes) without a line \
+                   break, passed on untranslated", max_capture);
+    }
+    Ok(())
+}
+
+fn forward_git_stderr(git_proc: &mut Child, translate: bool, hold_exec_failure: bool)
+        -> Option<thread::JoinHandle<io::Result<Option<Vec<u8>>>>> {
+    // stderr is forwarded in a separate thread, so that neither pipe
+    // can fill up and block the subprocess while the other one is read;
+    // without a pipe, git writes to the stderr of wslgit directly
+    git_proc.stderr.take().map(|git_stderr| thread::spawn(move || {
+        forward_stderr(BufReader::new(git_stderr), &mut io::stderr(), translate,
+                       hold_exec_failure)
+    }))
+}
+
+// like a process terminated by SIGPIPE in a shell
+const BROKEN_PIPE_EXIT_CODE: i32 = 128 + 13;
+
+fn output_error_exit_code(err: io::Error) -> Result<i32, WslgitError> {
+    // When the reader of the output exits early, e.g. in `wslgit log | head`,
+    // the rest of the output is not needed, which is no error of wslgit.
+    match err.kind() {
+        io::ErrorKind::BrokenPipe => Ok(BROKEN_PIPE_EXIT_CODE),
+        _ => Err(WslgitError::Output(err))
+    }
+}
+
+fn final_exit_code(status: ExitStatus, killed: bool, translated: io::Result<()>)
+        -> Result<i32, WslgitError> {
+    // The status of git is kept, unless git was killed because its output
+    // could not be written anymore. When git has exited on its own before,
+    // e.g. when the reader closed the pipe after all output was written,
+    // its status is forwarded, like in a shell.
+    match translated {
+        Ok(()) => Ok(status_to_exit_code(status)),
+        Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe && !killed =>
+            Ok(status_to_exit_code(status)),
+        Err(err) => output_error_exit_code(err)
+    }
+}
+
+fn print_stdout(text: &str) -> Result<i32, WslgitError> {
+    let mut stdout = io::stdout();
+    match writeln!(stdout, "{}", text).and_then(|_| stdout.flush()) {
+        Ok(()) => Ok(0),
+        Err(err) => output_error_exit_code(err)
+    }
+}
+
+fn test_connection(wsl_exe: &str, cmd_args: &[OsString], git_cmd: &str,
+                   mark_output: bool) -> Result<String, String> {
+    // returns the version printed by git, or why it could not be run
+    let output = Command::new(wsl_exe)
+        .args(cmd_args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| WslgitError::from_spawn_error(err, git_cmd).to_string())?;
+    let mut stdout = &output.stdout[..];
+    // the output of the startup files of the shell is no answer of git
+    let unmarked = if mark_output {
+        skip_to_marker(&mut stdout, OUTPUT_MARKER).map_err(|err| err.to_string())?
+    } else {
+        Vec::new()
+    };
+    // without marker, git did not even run, so nothing is removed
+    let version = String::from_utf8_lossy(
+        if unmarked.is_empty() { stdout } else { &unmarked[..] }).trim().to_owned();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(format!("'{}' failed with exit code {}: {}",
+                           git_cmd, status_to_exit_code(output.status), stderr));
+    }
+    if !version.starts_with("git version") {
+        return Err(format!("unexpected output of '{}': {}", git_cmd, version));
+    }
+    Ok(version)
+}
+
+fn main() {
+    match run() {
+        Ok(exit_code) => std::process::exit(exit_code),
+        Err(err) => {
+            eprintln!("wslgit: {}", err);
+            let distro = get_distro();
+            if let Some(hint) = distro_hint(&err, &distro, default_distro) {
+                eprintln!("wslgit: {}", hint);
+            }
+            if json_diagnostics_enabled() {
+                let args: Vec<OsString> = env::args_os().skip(1).collect();
+                eprintln!("{}", format_json_diagnostic(&err, distro.as_deref(), &args));
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run() -> Result<i32, WslgitError> {
+    let distro = get_distro();
+    let user = get_user();
+
+    // arguments are kept as OsString, so that arguments which are
+    // not valid unicode are passed on instead of causing a panic
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+
+    if is_wslgit_version(&args) {
+        let distro = describe_distro(&distro, distro.is_none().then(default_distro).flatten());
+        return print_stdout(&format_wslgit_version(
+            &distro, get_wsl_version(), get_shell_mode(), get_mount_root()));
+    }
+
+    if is_wslgit_test_connection(&args) {
+        // runs `git --version` the same way as any other command
+        let shell_mode = get_shell_mode();
+        let mark_output = shell_mode.reads_startup_files();
+        let cwd_unix = get_cwd_unix(
+            &env::current_dir().map_err(WslgitError::CurrentDir)?);
+        let (cmd_args, git_cmd) = build_cmd_args(
+            wsl_args(distro.clone(), user, &get_extra_wsl_args()), cwd_unix,
+            build_git_args(vec![OsString::from("--version")], true),
+            shell_mode, mark_output, false);
+        let result = get_wsl_exe(config::get().wsl_exe.as_deref())
+            .map_err(|err| err.to_string())
+            .and_then(|wsl_exe| test_connection(&wsl_exe, &cmd_args, &git_cmd, mark_output));
+        let distro = describe_distro(&distro, distro.is_none().then(default_distro).flatten());
+        let exit_code = print_stdout(&format_connection_test(&distro, shell_mode, &result))?;
+        return Ok(if result.is_ok() { exit_code } else { 1 });
+    }
+
+    if let Some((tool, tool_args)) = difftool_args(&args) {
+        let status = Command::new(tool)
+            .args(translate_tool_args(tool_args))
+            .status()
+            .map_err(|err| WslgitError::Execute(tool.to_string_lossy().into_owned(), err))?;
+        return Ok(status_to_exit_code(status));
+    }
+
+    if let Some(paths) = translate_paths_cmd(&args) {
+        if paths.is_empty() {
+            return Ok(0);
+        }
+        return print_stdout(&paths.join("\n"));
+    }
+
+    let cwd_unix = get_cwd_unix(
+        &env::current_dir().map_err(WslgitError::CurrentDir)?);
+
+    let lossy_args: Vec<String> = args.iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+
+    // WSLGIT_TRANSLATE takes precedence over the commands and options
+    // which decide about the translation otherwise
+    let translate_mode = get_translate_mode();
+    let start_editor = may_start_editor(&lossy_args);
+    let capture_output = should_capture_output(
+        translate_mode.translates_output()
+            && should_translate_output(&lossy_args, &get_no_translate_cmds()) && !start_editor,
+        io::stdout().is_terminal(),
+        force_translate() || prints_only_paths(&lossy_args));
+
+    let output_separator = get_output_separator(&args);
+    let leading_paths = prints_leading_paths(&lossy_args);
+
+    // setup stdin/stdout
+    let stdin_use = classify_stdin(&args, &lossy_args, get_stdin_mode(), start_editor);
+    let stdin_mode = || match stdin_use {
+        StdinUse::Null => Stdio::null(),
+        StdinUse::Inherit => Stdio::inherit(),
+        StdinUse::Pipe { .. } => Stdio::piped(),
+    };
+    let crlf_to_lf = stdin_use == StdinUse::Pipe { crlf_to_lf: true };
+    // messages piped in the code page of the console are passed as UTF-8
+    let input_codepage = match stdin_use {
+        StdinUse::Pipe { .. } if reads_text_from_stdin(&lossy_args) => console::input_codepage(),
+        _ => None
+    };
+
+    // kept for a native git, if WSL is not available
+    let fallback_git = config::get().fallback_git.as_deref();
+    let untranslated_args = args.clone();
+
+    // process git command arguments
+    let (args, response_files) = translate_input_args(args, translate_mode, &env::temp_dir())?;
+    let git_args = build_git_args(args, capture_output);
+
+    // stderr can only be merged into the captured output by a shell
+    let merge_stderr = capture_output && merge_stderr_enabled();
+    let shell_mode = match get_shell_mode() {
+        ShellMode::NoShell if merge_stderr => ShellMode::NonInteractive,
+        shell_mode => shell_mode
+    };
+    // frequently polled commands skip the startup files of the shell,
+    // which is still used if git cannot be run directly
+    let (shell_mode, fallback_mode) =
+        if uses_direct_path(&lossy_args, &get_direct_cmds(), shell_mode, merge_stderr) {
+            (ShellMode::NoShell, Some(shell_mode))
+        } else {
+            (shell_mode, None)
+        };
+    let extra_wsl_args = get_extra_wsl_args();
+    let build = |shell_mode: ShellMode| {
+        let mark_output = capture_output && shell_mode.reads_startup_files();
+        let (cmd_args, git_cmd) = build_cmd_args(
+            wsl_args(distro.clone(), user.clone(), &extra_wsl_args),
+            cwd_unix.clone(), git_args.clone(), shell_mode, mark_output, merge_stderr);
+        (cmd_args, git_cmd, mark_output)
+    };
+    let (cmd_args, _, _) = build(shell_mode);
+
+    let wsl_exe = get_wsl_exe(config::get().wsl_exe.as_deref())?;
+
+    if debug_enabled() {
+        // stderr, as stdout may be parsed by the caller
+        eprintln!("{}", format_debug_info(
+            &wsl_exe, &cmd_args, &distro, &user, capture_output));
+    }
+
+    if dry_run_enabled() {
+        return print_stdout(&format_wsl_cmd(&wsl_exe, &cmd_args));
+    }
+
+    // environment variables are only passed to WSL when listed in WSLENV
+    let wslenv = env::var("WSLENV").unwrap_or_default();
+    let mut git_env = translate_git_env(|name| env::var(name).ok());
+    git_env.extend(translate_wslenv_vars(&wslenv, |name| env::var(name).ok()));
+    git_env.extend(pager_env(config::get().pager.as_deref()));
+    git_env.extend(cwd_env(cwd_unix.as_deref()));
+    let console_size = if io::stdout().is_terminal() {
+        console::console_size()
+    } else {
+        None
+    };
+    git_env.extend(console::terminal_env(console_size, env::var("TERM").ok()));
+    let wslenv = build_wslenv(
+        &wslenv,
+        &git_env.iter().map(|(name, _)| name.as_str()).collect::<Vec<&str>>());
+
+    let mut script_file = None;
+    let mut launch = |launch_mode: ShellMode| {
+        let (mut cmd_args, git_cmd, mark_output) = build(launch_mode);
+        // git may not be found without the startup files of the shell
+        let direct = fallback_mode.is_some() && launch_mode == ShellMode::NoShell;
+        if launch_mode != shell_mode && debug_enabled() {
+            eprintln!("{}", format_debug_info(
+                &wsl_exe, &cmd_args, &distro, &user, capture_output));
+        }
+
+        // Windows limits the length of the command line, so very long commands
+        // are written to a script file, which is then executed by bash
+        if exceeds_cmd_line_limit(&cmd_args) {
+            let script_path = env::temp_dir()
+                .join(format!("wslgit-{}.sh", process::id()));
+            fs::write(&script_path,
+                      build_shell_cmd(cwd_unix.clone(), git_args.clone(),
+                                      mark_output, merge_stderr))
+                .map_err(WslgitError::ScriptFile)?;
+            cmd_args = build_script_cmd_args(
+                wsl_args(distro.clone(), user.clone(), &extra_wsl_args), launch_mode,
+                translate_path_to_unix(&script_path.to_string_lossy()).into_owned());
+            script_file = Some(script_path);
+        }
+
+        // setup the git subprocess launched inside WSL
+        let mut git_proc_setup = Command::new(&wsl_exe);
+        git_proc_setup.args(&cmd_args)
+            .envs(git_env.iter().cloned())
+            .env("WSLENV", &wslenv)
+            .stdin(stdin_mode());
+        if capture_output {
+            git_proc_setup.stdout(Stdio::piped());
+        }
+        if capture_output || direct {
+            git_proc_setup.stderr(Stdio::piped());
+        }
+        let git_proc = git_proc_setup.spawn()
+            .map_err(|err| WslgitError::from_spawn_error(err, &git_cmd))?;
+        Ok((git_proc, git_cmd, mark_output, direct))
+    };
+    // Right after boot or resume, wsl sometimes fails to start the first
+    // time. Once input was copied to git, it cannot be passed again.
+    let start_retries = get_start_retries();
+    let input_reusable = !matches!(stdin_use, StdinUse::Pipe { .. });
+    let mut attempt = 0;
+    let (mut launch_mode, mut launch_fallback) = (shell_mode, fallback_mode);
+    let (status, killed, translated) = loop {
+        let retry = |failure: StartFailure| {
+            should_retry_start(failure, attempt, start_retries, is_transient_start_failure)
+        };
+        let (mut git_proc, git_cmd, mark_output, direct) =
+            match spawn_with_fallback(launch_mode, launch_fallback, &mut launch) {
+                Err(WslgitError::Execute(_, ref err))
+                        if retry(StartFailure::Spawn(err.kind())) => {
+                    attempt += 1;
+                    thread::sleep(START_RETRY_DELAY * attempt);
+                    continue;
+                }
+                Err(ref err) if fallback_git.is_some()
+                        && err.spawn_failure().is_some_and(needs_fallback_git) => {
+                    let fallback_git = fallback_git.unwrap_or_default();
+                    break (run_fallback_git(fallback_git, &untranslated_args)?, false, Ok(()));
+                }
+                spawned => spawned?
+            };
+        forward_stdin(&mut git_proc, crlf_to_lf, input_codepage);
+
+        let stderr_thread = forward_git_stderr(&mut git_proc, capture_output, direct);
+        let translated = if capture_output {
+            translate_output(&mut git_proc, mark_output, leading_paths, output_separator,
+                             get_output_eol(), get_max_capture())
+        } else {
+            // the output of the subprocess is passed through unchanged
+            Ok(())
+        };
+        let (translated, exec_failure) = match stderr_thread.map(|thread| {
+            thread.join().expect("Failed to forward git error output")
+        }) {
+            Some(Ok(exec_failure)) => (translated, exec_failure),
+            Some(Err(err)) => (translated.and(Err(err)), None),
+            None => (translated, None)
+        };
+        // git would block once the pipe is full, as nothing reads it anymore
+        let killed = translated.is_err() && !matches!(git_proc.try_wait(), Ok(Some(_)));
+        if killed {
+            let _ = git_proc.kill();
+        }
+        let status = git_proc.wait()
+            .map_err(|err| WslgitError::Wait(git_cmd.clone(), err))?;
+        let shell_fallback = launch_fallback.filter(|_| translated.is_ok()
+            && falls_back_to_shell(exec_failure.is_some(), status.code(), input_reusable));
+        if let Some(fallback) = shell_fallback {
+            if debug_enabled() {
+                eprintln!("wslgit: git not found without the shell, retrying with the shell");
+            }
+            launch_mode = fallback;
+            launch_fallback = None;
+            continue;
+        }
+        let translated = match exec_failure {
+            // the error of wsl is forwarded after all
+            Some(exec_failure) => translated.and_then(|()| {
+                copy_stderr(&exec_failure[..], &mut io::stderr(), capture_output)
+            }),
+            None => translated
+        };
+        if translated.is_ok() && input_reusable && retry(StartFailure::Exit(status.code())) {
+            if debug_enabled() {
+                eprintln!("wslgit: wsl failed to start with exit code {}, retrying",
+                          status_to_exit_code(status));
+            }
+            attempt += 1;
+            thread::sleep(START_RETRY_DELAY * attempt);
+            continue;
+        }
+        if let Some(fallback_git) = fallback_git {
+            if translated.is_ok() && input_reusable
+                    && needs_fallback_git(StartFailure::Exit(status.code())) {
+                break (run_fallback_git(fallback_git, &untranslated_args)?, false, Ok(()));
+            }
+        }
+        break (status, killed, translated);
+    };
+
+    if let Some(script_path) = script_file {
+        // the script is not needed anymore, failing to remove it is harmless
+        let _ = fs::remove_file(script_path);
+    }
+    for response_file in response_files {
+        let _ = fs::remove_file(response_file);
+    }
+
+    if translated.is_ok() && status.code().is_none() {
+        eprintln!("wslgit: git was terminated by a signal");
+    }
+
+    // forward any exit code
+    final_exit_code(status, killed, translated)
+}
+
+
+#[cfg(test)]
+fn os_args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+#[cfg(test)]
+fn string_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|&arg| arg.to_owned()).collect()
+}
+
+#[test]
+fn output_translation_decision() {
+    assert!(should_translate_output(&string_args(&["rev-parse", "--show-toplevel"]), &[]));
+    assert!(should_translate_output(&string_args(&["-C", "/mnt/c", "remote", "-v"]), &[]));
+    assert!(should_translate_output(&string_args(&["status"]), &[]));
+    assert!(should_translate_output(&string_args(&["clean", "-n"]), &[]));
+    assert!(should_translate_output(
+        &string_args(&["format-patch", "-o", "C:\\p", "HEAD~3"]), &[]));
+    assert!(!should_translate_output(
+        &string_args(&["format-patch", "--stdout", "HEAD~3"]), &[]));
+    assert!(should_translate_output(
+        &string_args(&["worktree", "list", "--porcelain", "-z"]), &[]));
+    assert!(should_translate_output(
+        &string_args(&["submodule", "status", "--recursive"]), &[]));
+    assert!(should_translate_output(&string_args(&["grep", "-n", "--column", "foo"]), &[]));
+    assert!(!should_translate_output(&string_args(&["log", "--grep", "grep"]), &[]));
+    assert!(should_translate_output(&string_args(&["log", "--stat", "-3"]), &[]));
+    assert!(should_translate_output(
+        &string_args(&["apply", "--reject", "C:\\fix.diff"]), &[]));
+    assert!(should_translate_output(&string_args(&["am", "-3", "C:\\mail.mbox"]), &[]));
+    assert!(!should_translate_output(&string_args(&["am", "-i", "C:\\mail.mbox"]), &[]));
+    assert!(should_translate_output(
+        &string_args(&["ls-tree", "-r", "--full-name", "HEAD"]), &[]));
+    assert!(should_translate_output(
+        &string_args(&["cat-file", "--batch-check=%(objectname) %(rest)"]), &[]));
+    assert!(!should_translate_output(&string_args(&["cat-file", "--batch"]), &[]));
+    assert!(should_translate_output(&string_args(&["log", "--stat=80", "--format=%H"]), &[]));
+    assert!(!should_translate_output(&string_args(&["log", "--format=%H %s"]), &[]));
+    assert!(!should_translate_output(
+        &string_args(&["rev-parse", "--show-toplevel"]),
+        &string_args(&["rev-parse"])));
+    assert!(should_translate_output(
+        &string_args(&["config", "--list", "--show-origin"]), &[]));
+    assert!(!should_translate_output(&string_args(&["config", "--list"]), &[]));
+    assert!(!should_translate_output(
+        &string_args(&["config", "--list", "--show-origin"]), &string_args(&["config"])));
+    // binary output, even with a branch named like a translated command
+    assert!(!should_translate_output(&string_args(&["archive", "-o", "-", "remote"]), &[]));
+    assert!(!should_translate_output(
+        &string_args(&["bundle", "create", "-", "rev-parse"]), &[]));
+    // the command follows the options of git
+    let no_translate = string_args(&["show"]);
+    assert!(!should_translate_output(
+        &string_args(&["-c", "color.ui=always", "show", "HEAD:remote"]), &no_translate));
+    assert!(should_translate_output(
+        &string_args(&["-c", "x=y", "rev-parse", "--show-toplevel"]), &no_translate));
+    // not listed, but nothing to translate either
+    assert!(!should_translate_output(&string_args(&["-c", "x=y", "log"]), &no_translate));
+    assert!(!should_translate_output(&string_args(&["-c", "remote.origin.url=x"]), &[]));
+    assert!(!should_translate_output(&string_args(&["log", "remote"]), &[]));
+    // only commands are matched against WSLGIT_NO_TRANSLATE
+    assert!(should_translate_output(
+        &string_args(&["-c", "show", "remote", "-v"]), &no_translate));
+}
+
+#[test]
+fn direct_path_decision() {
+    let direct_cmds = default_direct_cmds(Some(WslVersion::Wsl2));
+    assert!(uses_direct_path(
+        &string_args(&["rev-parse", "--show-toplevel"]), &direct_cmds,
+        ShellMode::Interactive, false));
+    assert!(uses_direct_path(
+        &string_args(&["-C", "C:\\repo", "status", "-z"]), &direct_cmds,
+        ShellMode::NonInteractive, false));
+    assert!(!uses_direct_path(
+        &string_args(&["commit", "-m", "status"]), &direct_cmds,
+        ShellMode::Interactive, false));
+    // already without shell, or a shell is needed to merge stderr
+    assert!(!uses_direct_path(
+        &string_args(&["rev-parse"]), &direct_cmds, ShellMode::NoShell, false));
+    assert!(!uses_direct_path(
+        &string_args(&["rev-parse"]), &direct_cmds, ShellMode::Login, false));
+    assert!(!uses_direct_path(
+        &string_args(&["rev-parse"]), &direct_cmds, ShellMode::Interactive, true));
+    assert!(!uses_direct_path(
+        &string_args(&["rev-parse"]), &[], ShellMode::Interactive, false));
+    // more commands with WSL 1, where starting the shell is slow
+    assert!(!uses_direct_path(
+        &string_args(&["log", "-1"]), &direct_cmds, ShellMode::Interactive, false));
+    assert!(!uses_direct_path(
+        &string_args(&["log", "-1"]), &default_direct_cmds(None),
+        ShellMode::Interactive, false));
+    assert!(uses_direct_path(
+        &string_args(&["log", "-1"]), &default_direct_cmds(Some(WslVersion::Wsl1)),
+        ShellMode::Interactive, false));
+}
+
+#[test]
+fn direct_path_git_not_found() {
+    // wsl was started, but git was not found on the PATH without the shell
+    let not_found = b"<3>WSL (8) ERROR: CreateProcessEntryCommon:370: execvpe git failed 2\n";
+    let mut stderr = Vec::new();
+    let held = forward_stderr(&not_found[..], &mut stderr, false, true).unwrap();
+    assert_eq!(held.as_deref(), Some(&not_found[..]));
+    assert!(stderr.is_empty());
+    assert!(falls_back_to_shell(held.is_some(), Some(1), true));
+    assert!(is_exec_failure(b"execvpe(git) failed: No such file or directory\n"));
+    // input copied to git already cannot be passed to the shell again
+    assert!(!falls_back_to_shell(true, Some(1), false));
+    // errors of git itself are forwarded right away
+    let mut stderr = Vec::new();
+    let fatal = b"fatal: not a git repository: /mnt/c/x\nmore\n";
+    assert_eq!(forward_stderr(&fatal[..], &mut stderr, true, true).unwrap(), None);
+    assert_eq!(&stderr[..], &b"fatal: not a git repository: C:\\x\nmore\n"[..]);
+    assert!(!falls_back_to_shell(false, Some(128), true));
+    // the same message is no exec failure without the direct path
+    let mut stderr = Vec::new();
+    assert_eq!(forward_stderr(&not_found[..], &mut stderr, false, false).unwrap(), None);
+    assert_eq!(&stderr[..], &not_found[..]);
+}
+
+#[cfg(unix)]
+#[test]
+fn direct_path_spawned_git_not_found() {
+    // a process which is started, but fails like wsl without git
+    let mut child = Command::new("sh")
+        .args(["-c", "echo 'execvpe(git) failed: No such file or directory' >&2; exit 1"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let held = forward_git_stderr(&mut child, false, true).unwrap().join().unwrap().unwrap();
+    let status = child.wait().unwrap();
+    assert!(held.is_some());
+    assert!(falls_back_to_shell(held.is_some(), status.code(), true));
+}
+
+#[test]
+fn direct_path_fallback() {
+    let mut modes = Vec::new();
+    let result = spawn_with_fallback(
+        ShellMode::NoShell, Some(ShellMode::Interactive), |mode| {
+            modes.push(mode);
+            match mode {
+                ShellMode::NoShell => Err(WslgitError::Execute(
+                    "git rev-parse".to_owned(), io::Error::from(io::ErrorKind::Other))),
+                _ => Ok(mode)
+            }
+        });
+    assert_eq!(result.unwrap(), ShellMode::Interactive);
+    assert_eq!(modes, &[ShellMode::NoShell, ShellMode::Interactive]);
+    // the shell is only started once without direct path
+    let mut spawned = 0;
+    let result = spawn_with_fallback(ShellMode::Interactive, None, |_| -> Result<(), _> {
+        spawned += 1;
+        Err(WslgitError::WslNotFound)
+    });
+    assert!(result.is_err());
+    assert_eq!(spawned, 1);
+}
+
+#[test]
+fn start_retry_decision() {
+    let transient = |_| true;
+    let failure = StartFailure::Exit(Some(-1));
+    assert!(should_retry_start(failure, 0, 2, transient));
+    assert!(should_retry_start(failure, 1, 2, transient));
+    assert!(!should_retry_start(failure, 2, 2, transient));
+    assert!(!should_retry_start(failure, 0, 0, transient));
+    // failures of git itself are never retried
+    assert!(!should_retry_start(StartFailure::Exit(Some(128)), 0, 2, |_| false));
+
+    assert!(is_transient_start_failure(StartFailure::Exit(Some(-1))));
+    assert!(is_transient_start_failure(StartFailure::Spawn(io::ErrorKind::Other)));
+    assert!(!is_transient_start_failure(StartFailure::Exit(Some(1))));
+    assert!(!is_transient_start_failure(StartFailure::Exit(Some(128))));
+    assert!(!is_transient_start_failure(StartFailure::Exit(None)));
+    assert!(!is_transient_start_failure(StartFailure::Spawn(io::ErrorKind::NotFound)));
+}
+
+#[test]
+fn fallback_git_decision() {
+    assert!(needs_fallback_git(StartFailure::Spawn(io::ErrorKind::NotFound)));
+    assert!(needs_fallback_git(StartFailure::Exit(Some(-1))));
+    // errors of git itself, or of starting an existing wsl.exe
+    assert!(!needs_fallback_git(StartFailure::Exit(Some(1))));
+    assert!(!needs_fallback_git(StartFailure::Exit(Some(128))));
+    assert!(!needs_fallback_git(StartFailure::Exit(None)));
+    assert!(!needs_fallback_git(StartFailure::Spawn(io::ErrorKind::PermissionDenied)));
+    assert_eq!(WslgitError::WslNotFound.spawn_failure(),
+               Some(StartFailure::Spawn(io::ErrorKind::NotFound)));
+    assert_eq!(WslgitError::Output(io::Error::other("closed")).spawn_failure(), None);
+
+    let args: Vec<OsString> = ["-C", "C:\\repo", "log", "--format=%H %s", "--", "C:\\a b"]
+        .iter().map(OsString::from).collect();
+    let command = fallback_git_command("C:\\Program Files\\Git\\cmd\\git.exe", &args);
+    assert_eq!(command.get_program(), "C:\\Program Files\\Git\\cmd\\git.exe");
+    assert_eq!(command.get_args().collect::<Vec<_>>(),
+               args.iter().map(OsString::as_os_str).collect::<Vec<_>>());
+}
+
+#[test]
+fn version_or_help_detection() {
+    assert!(is_version_or_help(&os_args(&["--version"])));
+    assert!(is_version_or_help(&os_args(&["--version", "--build-options"])));
+    assert!(is_version_or_help(&os_args(&["version"])));
+    assert!(is_version_or_help(&os_args(&["commit", "-h"])));
+    assert!(is_version_or_help(&os_args(&["--help"])));
+    assert!(!is_version_or_help(&os_args(&["commit", "-F", "-"])));
+    assert!(!is_version_or_help(&os_args(&["log", "--grep", "version"])));
+    assert!(is_version_or_help(&[]));
+}
+
+#[test]
+fn stdin_mode_parsing() {
+    assert_eq!(parse_stdin_mode("inherit"), Some(StdinMode::Inherit));
+    assert_eq!(parse_stdin_mode("pipe"), Some(StdinMode::Pipe));
+    assert_eq!(parse_stdin_mode("lf"), Some(StdinMode::Lf));
+    assert_eq!(parse_stdin_mode("crlf"), None);
+    assert!(reads_text_from_stdin(&string_args(&["commit", "-F", "-"])));
+    assert!(reads_text_from_stdin(&string_args(&["tag", "-a", "v1", "--file=-"])));
+    assert!(!reads_text_from_stdin(&string_args(&["hash-object", "--stdin"])));
+    assert!(!reads_text_from_stdin(&string_args(&["commit", "-m", "-"])));
+    assert!(!reads_text_from_stdin(&string_args(&["log", "--grep", "commit"])));
+}
+
+#[test]
+fn stdin_classification() {
+    let classify = |args: &[&str], configured: StdinMode, start_editor: bool| {
+        classify_stdin(&os_args(args), &string_args(args), configured, start_editor)
+    };
+    assert_eq!(classify(&["--version"], StdinMode::Inherit, false), StdinUse::Null);
+    assert_eq!(classify(&["--version"], StdinMode::Pipe, false), StdinUse::Null);
+    assert_eq!(classify(&[], StdinMode::Inherit, false), StdinUse::Null);
+    assert_eq!(classify(&["commit"], StdinMode::Inherit, true), StdinUse::Inherit);
+    assert_eq!(classify(&["commit", "-F", "-"], StdinMode::Inherit, false),
+               StdinUse::Inherit);
+    assert_eq!(classify(&["log"], StdinMode::Inherit, false), StdinUse::Inherit);
+
+    // with a pipe, only commands reading from stdin use it
+    assert_eq!(classify(&["log"], StdinMode::Pipe, false), StdinUse::Inherit);
+    assert_eq!(classify(&["commit"], StdinMode::Lf, true), StdinUse::Inherit);
+    assert_eq!(classify(&["commit", "-F", "-"], StdinMode::Pipe, false),
+               StdinUse::Pipe { crlf_to_lf: false });
+    assert_eq!(classify(&["commit", "-F", "-"], StdinMode::Lf, false),
+               StdinUse::Pipe { crlf_to_lf: true });
+    assert_eq!(classify(&["hash-object", "--stdin"], StdinMode::Lf, false),
+               StdinUse::Pipe { crlf_to_lf: false });
+    assert_eq!(classify(&["cat-file", "--batch-check"], StdinMode::Pipe, false),
+               StdinUse::Pipe { crlf_to_lf: false });
+    assert_eq!(classify(&["apply"], StdinMode::Pipe, false),
+               StdinUse::Pipe { crlf_to_lf: false });
+
+    // only the git command and its options count, not other arguments
+    assert_eq!(classify(&["log", "--grep", "commit"], StdinMode::Pipe, false),
+               StdinUse::Inherit);
+    assert_eq!(classify(&["branch", "tag"], StdinMode::Pipe, false), StdinUse::Inherit);
+    assert_eq!(classify(&["checkout", "-"], StdinMode::Pipe, false), StdinUse::Inherit);
+    assert_eq!(classify(&["diff", "--", "-"], StdinMode::Pipe, false), StdinUse::Inherit);
+    assert_eq!(classify(&["log", "--", "--stdin"], StdinMode::Pipe, false),
+               StdinUse::Inherit);
+    assert_eq!(classify(&["commit", "-m", "x"], StdinMode::Pipe, false),
+               StdinUse::Inherit);
+}
+
+#[test]
+fn wslgit_version_detection() {
+    assert!(is_wslgit_version(&os_args(&["--wslgit-version"])));
+    assert!(!is_wslgit_version(&os_args(&["--version"])));
+    assert!(!is_wslgit_version(&os_args(&["log", "--wslgit-version"])));
+    assert!(!is_wslgit_version(&[]));
+    assert_eq!(
+        format_wslgit_version("Debian", Some(WslVersion::Wsl2), ShellMode::Login, ""),
+        format!("wslgit {}\ndistro: Debian\nwsl: 2\nshell: login\nmount root: /",
+                env!("CARGO_PKG_VERSION")));
+    assert_eq!(
+        format_wslgit_version("Ubuntu (default)", None, ShellMode::Interactive, "/mnt"),
+        format!("wslgit {}\ndistro: Ubuntu (default)\nwsl: unknown\nshell: interactive\nmount root: /mnt",
+                env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn connection_test_output() {
+    assert!(is_wslgit_test_connection(&os_args(&["--wslgit-test-connection"])));
+    assert!(!is_wslgit_test_connection(&os_args(&["status", "--wslgit-test-connection"])));
+    assert_eq!(
+        format_connection_test("Debian", ShellMode::Login,
+                               &Ok("git version 2.39.2".to_owned())),
+        "wslgit: connection ok\ndistro: Debian\nshell: login\ngit: git version 2.39.2");
+    assert_eq!(
+        format_connection_test(&describe_distro(&None, None), ShellMode::Interactive,
+                               &Err(WslgitError::WslNotFound.to_string())),
+        "wslgit: connection failed\ndistro: default (none installed or wsl not found)\n\
+         shell: interactive\nerror: WSL (wsl.exe) not found on PATH");
+}
+
+#[test]
+fn distro_error_hint() {
+    let err = || WslgitError::Execute("git status".to_owned(), io::Error::other("failed"));
+    assert_eq!(distro_hint(&err(), &None, || Some("Ubuntu".to_owned())),
+               Some("using the default distribution 'Ubuntu'".to_owned()));
+    assert_eq!(distro_hint(&err(), &None, || None),
+               Some("no WSL distribution is installed, install one or set WSLGIT_DISTRO"
+                    .to_owned()));
+    assert_eq!(distro_hint(&err(), &Some("Debian".to_owned()), || unreachable!()), None);
+    assert_eq!(distro_hint(&WslgitError::WslNotFound, &None, || unreachable!()), None);
+}
+
+#[test]
+fn distro_description() {
+    assert_eq!(describe_distro(&Some("Debian".to_owned()), Some("Ubuntu".to_owned())),
+               "Debian");
+    assert_eq!(describe_distro(&None, Some("Ubuntu".to_owned())), "Ubuntu (default)");
+    assert_eq!(describe_distro(&None, None), "default (none installed or wsl not found)");
+}
+
+#[cfg(unix)]
+#[test]
+fn connection_test_spawn_failure() {
+    let result = test_connection(
+        "/nonexistent/wsl", &[OsString::from("--exec")], "git --version", false);
+    assert_eq!(result, Err("WSL (wsl.exe) not found on PATH".to_owned()));
+}
+
+#[test]
+fn translate_paths_cmd_output() {
+    assert_eq!(translate_paths_cmd(&os_args(&["wslgit-untranslate", "/mnt/c/x", "src/x"])),
+               Some(vec!["C:\\x".to_owned(), "src/x".to_owned()]));
+    assert_eq!(translate_paths_cmd(&os_args(&["wslgit-translate"])), Some(Vec::new()));
+    assert_eq!(translate_paths_cmd(&os_args(&["status", "wslgit-translate"])), None);
+    assert_eq!(translate_paths_cmd(&[]), None);
+    assert_eq!(
+        translate_paths_cmd(&os_args(&[
+            "wslgit-translate", "C:\\Users\\me", "\\\\server\\share\\repo", "src\\new.rs"])),
+        Some(vec!["/mnt/c/Users/me".to_owned(), "//server/share/repo".to_owned(),
+                  "src\\new.rs".to_owned()]));
+}
+
+#[test]
+fn difftool_arg_translation() {
+    let cmd_args = os_args(&["--wslgit-as-difftool", "C:\\Tools\\meld.exe",
+                          "/mnt/c/repo/a.txt", "/tmp/a_REMOTE.txt", "--label=x"]);
+    let (tool, tool_args) = difftool_args(&cmd_args).unwrap();
+    assert_eq!(tool, "C:\\Tools\\meld.exe");
+    assert_eq!(translate_tool_args(tool_args),
+               os_args(&["C:\\repo\\a.txt", "/tmp/a_REMOTE.txt", "--label=x"]));
+    assert_eq!(translate_tool_args(&os_args(&["/mnt/d/My Files/b.txt"])),
+               os_args(&["D:\\My Files\\b.txt"]));
+    assert!(difftool_args(&os_args(&["--wslgit-as-difftool"])).is_none());
+    assert!(difftool_args(&os_args(&["difftool", "--wslgit-as-difftool", "x"])).is_none());
+}
+
+#[test]
+fn git_env_translation() {
+    let git_env = translate_git_env(|name| match name {
+        "GIT_DIR" => Some("C:\\repo\\.git".to_owned()),
+        "GIT_ALTERNATE_OBJECT_DIRECTORIES" => Some("C:\\a;D:\\b".to_owned()),
+        "GIT_EDITOR" => Some("\"C:\\Program Files\\Tool\\t.exe\" --wait".to_owned()),
+        "GIT_SSH_COMMAND" => Some("ssh -i ~/.ssh/key".to_owned()),
+        _ => None
+    });
+    assert_eq!(git_env, &[
+        ("GIT_DIR".to_owned(), "/mnt/c/repo/.git".to_owned()),
+        ("GIT_ALTERNATE_OBJECT_DIRECTORIES".to_owned(), "/mnt/c/a:/mnt/d/b".to_owned()),
+        ("GIT_EDITOR".to_owned(), "'/mnt/c/Program Files/Tool/t.exe' --wait".to_owned()),
+        ("GIT_SSH_COMMAND".to_owned(), "ssh -i ~/.ssh/key".to_owned())]);
+}
+
+#[test]
+fn pager_env_vars() {
+    assert_eq!(pager_env(Some("none")), &[("GIT_PAGER".to_owned(), "cat".to_owned())]);
+    assert_eq!(pager_env(Some("cat")), &[("GIT_PAGER".to_owned(), "cat".to_owned())]);
+    assert_eq!(pager_env(Some("less -R")),
+               &[("GIT_PAGER".to_owned(), "less -R".to_owned())]);
+    // core.pager of git applies
+    assert!(pager_env(None).is_empty());
+    assert_eq!(build_wslenv("", &["GIT_PAGER"]), "GIT_PAGER");
+}
+
+#[test]
+fn wslenv_parsing() {
+    assert_eq!(
+        parse_wslenv("BASH_ENV/up:PLAIN::LIST/l"),
+        &[("BASH_ENV", "up"), ("PLAIN", ""), ("LIST", "l")]);
+    assert!(parse_wslenv("").is_empty());
+}
+
+#[test]
+fn wslenv_var_translation() {
+    let lookup = |name: &str| match name {
+        "MY_FILE" => Some("C:\\cfg\\my.conf".to_owned()),
+        "MY_DIRS" => Some("C:\\a;D:\\b".to_owned()),
+        "TO_WIN" => Some("C:\\win".to_owned()),
+        _ => None
+    };
+    assert_eq!(
+        translate_wslenv_vars("MY_FILE/p:MY_DIRS/l:TO_WIN/pw:OTHER", lookup),
+        &[("MY_FILE".to_owned(), "/mnt/c/cfg/my.conf".to_owned()),
+          ("MY_DIRS".to_owned(), "/mnt/c/a:/mnt/d/b".to_owned())]);
+}
+
+#[test]
+fn wslenv_building() {
+    assert_eq!(build_wslenv("", &["GIT_DIR"]), "GIT_DIR");
+    assert_eq!(build_wslenv("", &[]), "");
+    assert_eq!(
+        build_wslenv("BASH_ENV/up:GIT_DIR/p", &["GIT_DIR", "GIT_WORK_TREE"]),
+        "BASH_ENV/up:GIT_DIR:GIT_WORK_TREE");
+}
+
+#[test]
+fn output_separator() {
+    assert_eq!(get_output_separator(&os_args(&["status", "-z"])), b'\0');
+    assert_eq!(get_output_separator(&os_args(&["ls-files", "--null"])), b'\0');
+    assert_eq!(get_output_separator(&os_args(&["status"])), b'\n');
+}
+
+#[test]
+fn dry_run_cmd() {
+    let git_args: Vec<OsString> = ["git", "commit", "-m", "hi there"]
+        .iter().map(OsString::from).collect();
+    let (cmd_args, git_cmd) = build_cmd_args(
+        wsl_args(Some("Debian".to_owned()), None, &[]),
+        Some("/mnt/c/repo".to_owned()), git_args.clone(),
+        ShellMode::Interactive, false, false);
+    assert_eq!(git_cmd, "cd /mnt/c/repo && git commit -m 'hi there'");
+    assert_eq!(
+        format_wsl_cmd("wsl", &cmd_args),
+        r#"wsl -d Debian --exec bash -ic 'cd /mnt/c/repo && git commit -m '\''hi there'\'''"#);
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), None, git_args, ShellMode::NoShell, false, false);
+    assert_eq!(format_wsl_cmd("wsl", &cmd_args), "wsl --exec git commit -m 'hi there'");
+}
+
+#[test]
+fn bare_git_cmd() {
+    // `wslgit` alone runs `git` alone, which prints its usage and exits
+    // with its own code, without input and without capturing the output
+    let args: Vec<OsString> = Vec::new();
+    assert!(is_version_or_help(&args));
+    assert!(!should_translate_output(&[], &[]));
+    let git_args = build_git_args(translate_args_to_unix(args), false);
+    assert_eq!(git_args, &["git"]);
+    let (cmd_args, git_cmd) = build_cmd_args(
+        Vec::new(), Some("/mnt/c/repo".to_owned()), git_args.clone(),
+        ShellMode::Interactive, false, false);
+    assert_eq!(git_cmd, "cd /mnt/c/repo && git");
+    assert_eq!(&cmd_args[..3], &["--exec", "bash", "-ic"]);
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), None, git_args, ShellMode::NoShell, false, false);
+    assert_eq!(cmd_args, &["--exec", "git"]);
+}
+
+#[test]
+fn glob_protection() {
+    let git_args: Vec<OsString> = ["git", "log", "--", "*.rs", "foo?.txt", "src/[abc].rs"]
+        .iter().map(OsString::from).collect();
+    let (cmd_args, git_cmd) = build_cmd_args(
+        Vec::new(), Some("/mnt/c/repo".to_owned()), git_args.clone(),
+        ShellMode::NonInteractive, false, false);
+    assert_eq!(git_cmd, "cd /mnt/c/repo && git log -- '*.rs' 'foo?.txt' 'src/[abc].rs'");
+    assert_eq!(&cmd_args[..3], &["--exec", "bash", "-c"]);
+    // without a shell, nothing is expanded
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), None, git_args, ShellMode::NoShell, false, false);
+    assert_eq!(&cmd_args[4..], &["*.rs", "foo?.txt", "src/[abc].rs"]);
+}
+
+#[test]
+fn shell_mode_cmd_args() {
+    let git_args: Vec<OsString> = ["git", "status"]
+        .iter().map(OsString::from).collect();
+    let cmd_args = |shell_mode: ShellMode| -> Vec<OsString> {
+        build_cmd_args(
+            Vec::new(), Some("/mnt/c/repo".to_owned()),
+            git_args.clone(), shell_mode, false, false).0
+    };
+    assert_eq!(
+        cmd_args(ShellMode::Interactive),
+        &["--exec", "bash", "-ic", "cd /mnt/c/repo && git status"]);
+    assert_eq!(
+        cmd_args(ShellMode::Login),
+        &["--exec", "bash", "-lc", "cd /mnt/c/repo && git status"]);
+    assert_eq!(
+        cmd_args(ShellMode::NonInteractive),
+        &["--exec", "bash", "-c", "cd /mnt/c/repo && git status"]);
+    assert_eq!(
+        cmd_args(ShellMode::NoShell),
+        &["--cd", "/mnt/c/repo", "--exec", "git", "status"]);
+    assert_eq!(parse_shell_mode("login"), Some(ShellMode::Login));
+    assert_eq!(parse_shell_mode("none"), Some(ShellMode::NoShell));
+    assert_eq!(parse_shell_mode("zsh"), None);
+}
+
+#[test]
+fn login_shell_cmd() {
+    let git_args: Vec<OsString> = ["git", "--no-pager", "log", "--format=%an's $x"]
+        .iter().map(OsString::from).collect();
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), Some("/mnt/c/my repo".to_owned()), git_args,
+        ShellMode::Login, ShellMode::Login.reads_startup_files(), false);
+    assert_eq!(&cmd_args[..3], &["--exec", "bash", "-lc"]);
+    // the output of .profile is removed from captured output as well
+    assert_eq!(
+        cmd_args[3],
+        OsString::from(format!(
+            r#"cd '/mnt/c/my repo' && echo {} && git --no-pager log '--format=%an'\''s $x'"#,
+            OUTPUT_MARKER)));
+}
+
+#[cfg(unix)]
+#[test]
+fn same_args_with_and_without_shell() {
+    // the arguments git gets from bash, here printed instead, are exactly
+    // those which it gets from wsl directly without a shell
+    let args: Vec<OsString> = [
+            "commit", "-m", "\"quoted\"", "--", "'my file'", "foo\" bar", "$HOME", "*.rs",
+            "it's", ""]
+        .iter().map(OsString::from).collect();
+    let git_args = translate_args_to_unix(args);
+    let (direct_args, _) = build_cmd_args(
+        Vec::new(), None, git_args.clone(), ShellMode::NoShell, false, false);
+    let mut print_args = vec![OsString::from("printf"), OsString::from("%s\\0")];
+    print_args.extend(git_args.iter().cloned());
+    let output = Command::new("sh")
+        .args(["-c", &build_shell_cmd(None, print_args, false, false)])
+        .output()
+        .unwrap();
+    let shell_args: Vec<OsString> = output.stdout.split(|&c| c == 0)
+        .map(|arg| OsString::from(String::from_utf8_lossy(arg).into_owned()))
+        .collect();
+    assert_eq!(&direct_args[1..], &shell_args[..git_args.len()]);
+    assert_eq!(&direct_args[1..], &git_args[..]);
+}
+
+#[test]
+fn long_cmd_line_fallback() {
+    let mut git_args = vec![OsString::from("git"), OsString::from("add")];
+    git_args.extend((0..2000).map(|i| {
+        OsString::from(format!("/mnt/c/some/long/path/to/file{}.txt", i))
+    }));
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), Some("/mnt/c/repo".to_owned()), git_args.clone(),
+        ShellMode::Interactive, false, false);
+    assert!(exceeds_cmd_line_limit(&cmd_args));
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), Some("/mnt/c/repo".to_owned()), git_args[..10].to_vec(),
+        ShellMode::Interactive, false, false);
+    assert!(!exceeds_cmd_line_limit(&cmd_args));
+    assert_eq!(
+        build_script_cmd_args(
+            wsl_args(Some("Debian".to_owned()), None, &[]), ShellMode::Interactive,
+            "/mnt/c/Temp/wslgit-1.sh".to_owned()),
+        &["-d", "Debian", "--exec", "bash", "-ic", ". /mnt/c/Temp/wslgit-1.sh"]);
+    assert_eq!(
+        build_script_cmd_args(
+            Vec::new(), ShellMode::NoShell, "/mnt/c/Temp/wslgit-1.sh".to_owned()),
+        &["--exec", "bash", "-c", ". /mnt/c/Temp/wslgit-1.sh"]);
+}
+
+#[test]
+fn debug_info_formatting() {
+    let cmd_args: Vec<OsString> = ["-d", "Debian", "bash", "-ic", "git remote -v"]
+        .iter().map(OsString::from).collect();
+    let info = format_debug_info(
+        "wsl", &cmd_args, &Some("Debian".to_owned()), &None, true);
+    assert!(info.contains("wsl -d Debian bash -ic 'git remote -v'"));
+    assert!(info.contains("distro: Debian"));
+    assert!(info.contains("user: default"));
+    assert!(info.contains("output: captured"));
+    let info = format_debug_info("wsl", &cmd_args, &None, &None, false);
+    assert!(info.contains("output: passed through"));
+}
+
+#[test]
+fn output_capture_decision() {
+    assert!(should_capture_output(true, false, false));
+    assert!(!should_capture_output(true, true, false));
+    assert!(should_capture_output(true, true, true));
+    assert!(!should_capture_output(false, false, false));
+    assert!(!should_capture_output(false, true, true));
+}
+
+#[test]
+fn path_only_output_detection() {
+    assert!(prints_only_paths(&string_args(&["rev-parse", "--show-toplevel"])));
+    assert!(prints_only_paths(&string_args(&["-C", "sub", "rev-parse", "--git-dir"])));
+    assert!(prints_only_paths(&string_args(&["rev-parse", "--git-common-dir"])));
+    assert!(prints_only_paths(&string_args(&["rev-parse", "--absolute-git-dir"])));
+    assert!(!prints_only_paths(&string_args(&["rev-parse", "HEAD"])));
+    assert!(!prints_only_paths(&string_args(&["log", "--grep", "--git-dir"])));
+}
+
+#[test]
+fn editor_detection() {
+    assert!(may_start_editor(&string_args(&["commit"])));
+    assert!(may_start_editor(&string_args(&["commit", "--amend"])));
+    assert!(may_start_editor(&string_args(&["commit", "-m", "x", "-e"])));
+    assert!(!may_start_editor(&string_args(&["commit", "-m", "x"])));
+    assert!(may_start_editor(&string_args(&["-C", "sub", "commit"])));
+    assert!(!may_start_editor(&string_args(&["commit", "-am", "x"])));
+    // a key ID attached to -S or -u is no message option
+    assert!(may_start_editor(&string_args(&["commit", "-SmyKey"])));
+    assert!(may_start_editor(&string_args(&["commit", "-aSCAFE"])));
+    assert!(may_start_editor(&string_args(&["tag", "-s", "-uMyKey", "v1"])));
+    assert!(!may_start_editor(&string_args(&["commit", "--message=x"])));
+    assert!(!may_start_editor(&string_args(&["commit", "-F", "-"])));
+    assert!(!may_start_editor(&string_args(&["commit", "--amend", "--no-edit"])));
+    assert!(may_start_editor(&string_args(&["rebase", "-i", "HEAD~3"])));
+    assert!(!may_start_editor(&string_args(&["rebase", "main"])));
+    assert!(may_start_editor(&string_args(&["tag", "-a", "v1"])));
+    assert!(!may_start_editor(&string_args(&["tag", "-a", "v1", "-m", "x"])));
+    assert!(!may_start_editor(&string_args(&["tag", "v1"])));
+    assert!(!may_start_editor(&string_args(&["log", "commit"])));
+}
+
+#[test]
+fn no_pager_for_captured_output() {
+    let capture = should_translate_output(&string_args(&["remote", "-v"]), &[]);
+    assert_eq!(
+        build_git_args(os_args(&["remote", "-v"]), capture),
+        &["git", "--no-pager", "remote", "-v"]);
+    let capture = should_translate_output(&string_args(&["log", "-1"]), &[]);
+    assert_eq!(build_git_args(os_args(&["log", "-1"]), capture), &["git", "log", "-1"]);
+    // captured, e.g. with WSLGIT_FORCE_TRANSLATE
+    assert_eq!(build_git_args(os_args(&["log"]), true), &["git", "--no-pager", "log"]);
+    assert_eq!(build_git_args(os_args(&["--no-pager", "log"]), true),
+               &["git", "--no-pager", "log"]);
+    assert_eq!(build_git_args(os_args(&["-P", "log"]), true), &["git", "-P", "log"]);
+    assert_eq!(build_git_args(os_args(&["-c", "x=y", "-p", "log"]), true),
+               &["git", "-c", "x=y", "-p", "log"]);
+    // options of the git command are no options of git
+    assert_eq!(build_git_args(os_args(&["log", "-p", "-P"]), true),
+               &["git", "--no-pager", "log", "-p", "-P"]);
+}
+
+#[test]
+fn working_dir_translation() {
+    assert_eq!(
+        get_cwd_unix(Path::new("C:\\Users\\me\\my repo")),
+        Some("/mnt/c/Users/me/my repo".to_owned()));
+    assert_eq!(
+        get_cwd_unix(Path::new("\\\\server\\share\\repo")),
+        None);
+}
+
+#[test]
+fn merged_stderr_cmd() {
+    let git_args = vec![OsString::from("git"), OsString::from("remote")];
+    assert_eq!(
+        build_shell_cmd(Some("/mnt/c".to_owned()), git_args.clone(), true, true),
+        format!("cd /mnt/c && echo {} && git remote 2>&1", OUTPUT_MARKER));
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), None, git_args, ShellMode::NonInteractive, false, true);
+    assert_eq!(cmd_args, &["--exec", "bash", "-c", "git remote 2>&1"]);
+}
+
+#[test]
+fn working_dir_shell_cmd() {
+    let git_args = vec![OsString::from("git"), OsString::from("status")];
+    assert_eq!(
+        build_shell_cmd(Some("/mnt/c/my repo".to_owned()), git_args.clone(), false, false),
+        "cd '/mnt/c/my repo' && git status");
+    assert_eq!(build_shell_cmd(None, git_args.clone(), false, false), "git status");
+    assert_eq!(
+        build_shell_cmd(Some("/mnt/c".to_owned()), git_args, true, false),
+        format!("cd /mnt/c && echo {} && git status", OUTPUT_MARKER));
+}
+
+#[test]
+fn working_dir_env() {
+    let cwd = "/mnt/c/my repo";
+    let (cmd_args, _) = build_cmd_args(
+        Vec::new(), Some(cwd.to_owned()),
+        vec![OsString::from("git"), OsString::from("status")],
+        ShellMode::NoShell, false, false);
+    assert_eq!(cmd_args, &["--cd", cwd, "--exec", "git", "status"]);
+    let env = cwd_env(Some(cwd));
+    assert_eq!(env, vec![("PWD".to_owned(), cwd.to_owned()),
+                         ("OLDPWD".to_owned(), cwd.to_owned())]);
+    assert_eq!(build_wslenv("", &env.iter().map(|(name, _)| name.as_str())
+                                     .collect::<Vec<&str>>()),
+               "PWD:OLDPWD");
+    // wsl starts in the home directory, which PWD names already
+    assert!(cwd_env(None).is_empty());
+}
+
+#[test]
+fn wsl_distro_args() {
+    assert!(wsl_args(None, None, &[]).is_empty());
+    let cmd_args = wsl_args(Some("Debian".to_owned()), None, &[]);
+    assert_eq!(&cmd_args[..2], &["-d", "Debian"]);
+}
+
+#[test]
+fn extra_wsl_args() {
+    let extra = config::parse_args("--cd /tmp");
+    let (cmd_args, _) = build_cmd_args(
+        wsl_args(Some("Debian".to_owned()), Some("me".to_owned()), &extra),
+        None, vec![OsString::from("git"), OsString::from("status")],
+        ShellMode::Interactive, false, false);
+    assert_eq!(cmd_args,
+               &["-d", "Debian", "-u", "me", "--cd", "/tmp",
+                 "--exec", "bash", "-ic", "git status"]);
+    let (cmd_args, _) = build_cmd_args(
+        wsl_args(None, None, &extra), Some("/mnt/c/repo".to_owned()),
+        vec![OsString::from("git")], ShellMode::NoShell, false, false);
+    assert_eq!(cmd_args, &["--cd", "/tmp", "--cd", "/mnt/c/repo", "--exec", "git"]);
+}
+
+#[test]
+fn wsl_user_args() {
+    assert_eq!(
+        wsl_args(None, Some("me".to_owned()), &[]),
+        &["-u", "me"]);
+    assert_eq!(
+        wsl_args(Some("Debian".to_owned()), Some("me".to_owned()), &[]),
+        &["-d", "Debian", "-u", "me"]);
+}
+
+#[test]
+fn response_file_translation() {
+    let dir = env::temp_dir().join(format!("wslgit-response-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let response_file = dir.join("args.txt");
+    fs::write(&response_file, "C:\\repo\\file.txt\r\n--cached\n").unwrap();
+    let args = vec![
+        OsString::from("add"),
+        OsString::from(format!("@{}", response_file.display())),
+        OsString::from("@{upstream}")];
+    let (translated_args, translated_files) =
+        translate_response_files(args, &dir).unwrap();
+    let contents = fs::read_to_string(&translated_files[0]).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(contents, "/mnt/c/repo/file.txt\n--cached\n");
+    assert_eq!(translated_files.len(), 1);
+    assert_eq!(translated_args[0], "add");
+    assert_eq!(translated_args[1], OsString::from(format!(
+        "@{}", translate_path_to_unix(&translated_files[0].to_string_lossy()))));
+    // not a file, e.g. a revision
+    assert_eq!(translated_args[2], "@{upstream}");
+}
+
+#[test]
+fn translate_mode_switch() {
+    assert_eq!(parse_translate_mode("off"), Some(TranslateMode::Off));
+    assert_eq!(parse_translate_mode("in"), Some(TranslateMode::In));
+    assert_eq!(parse_translate_mode("out"), Some(TranslateMode::Out));
+    assert_eq!(parse_translate_mode("both"), Some(TranslateMode::Both));
+    assert_eq!(parse_translate_mode("on"), None);
+
+    let dir = env::temp_dir();
+    let translate = |mode: TranslateMode| {
+        let args = vec![OsString::from("clone"), OsString::from("file:///C:/repo.git")];
+        let (translated, response_files) = translate_input_args(args, mode, &dir).unwrap();
+        assert!(response_files.is_empty());
+        // the output of `git remote -v`, as consumed by another program
+        let captured = should_capture_output(
+            mode.translates_output()
+                && should_translate_output(&["remote".to_owned(), "-v".to_owned()], &[]),
+            false, false);
+        (translated[1].clone(), captured)
+    };
+    let translated_url = translate_path_to_unix("file:///C:/repo.git").into_owned();
+    assert_eq!(translate(TranslateMode::Off), (OsString::from("file:///C:/repo.git"), false));
+    assert_eq!(translate(TranslateMode::In), (OsString::from(&translated_url), false));
+    assert_eq!(translate(TranslateMode::Out), (OsString::from("file:///C:/repo.git"), true));
+    assert_eq!(translate(TranslateMode::Both), (OsString::from(&translated_url), true));
+}
+
+#[test]
+fn wsl_exe_override() {
+    assert_eq!(get_wsl_exe(None).unwrap(), "wsl");
+    let exe = env::current_exe().unwrap().to_string_lossy().into_owned();
+    assert_eq!(get_wsl_exe(Some(&exe)).unwrap(), exe);
+    let cmd_args = vec![OsString::from("--exec"), OsString::from("git")];
+    assert_eq!(format_wsl_cmd("C:/Windows/System32/wsl.exe", &cmd_args),
+               "C:/Windows/System32/wsl.exe --exec git");
+    assert_eq!(
+        get_wsl_exe(Some("C:/missing/wsl.exe")).unwrap_err().to_string(),
+        "WSL launcher 'C:/missing/wsl.exe' from WSLGIT_WSL_EXE not found");
+}
+
+#[test]
+fn error_messages() {
+    let not_found = io::Error::from(io::ErrorKind::NotFound);
+    assert_eq!(
+        WslgitError::from_spawn_error(not_found, "git status").to_string(),
+        "WSL (wsl.exe) not found on PATH");
+    let denied = io::Error::new(io::ErrorKind::PermissionDenied, "access denied");
+    assert_eq!(
+        WslgitError::from_spawn_error(denied, "git status").to_string(),
+        "Failed to execute command 'git status': access denied");
+    let broken = io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe");
+    assert_eq!(
+        WslgitError::Output(broken).to_string(),
+        "Failed to write git output: broken pipe");
+}
+
+#[test]
+fn json_diagnostics() {
+    let args = vec![OsString::from("status"), OsString::from("--short")];
+    assert_eq!(
+        format_json_diagnostic(&WslgitError::WslNotFound, None, &args),
+        concat!(r#"{"error":"WSL (wsl.exe) not found on PATH","kind":"wsl_not_found","#,
+                r#""distro":null,"command":"git status --short"}"#));
+    let err = WslgitError::ResponseFile(io::Error::other("no \"C:\\args\"\n"));
+    assert_eq!(
+        format_json_diagnostic(&err, Some("Ubuntu"),
+                               &[OsString::from("add"), OsString::from("@args")]),
+        concat!(r#"{"error":"Failed to translate response file: no \"C:\\args\"\n","#,
+                r#""kind":"response_file","distro":"Ubuntu","command":"git add @args"}"#));
+    let err = WslgitError::Execute("git status".to_owned(), io::Error::other("denied"));
+    assert!(format_json_diagnostic(&err, None, &args).ends_with(r#""command":"git status"}"#));
+    assert_eq!(json_string("a\u{1}b"), r#""a\u0001b""#);
+}
+
+#[test]
+fn broken_pipe_exit() {
+    struct ClosedPipe;
+    impl Write for ClosedPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let err = translate_records(&b"/mnt/c/a\n/mnt/c/b\n"[..], &mut ClosedPipe, b'\n', LineEnding::Keep)
+        .unwrap_err();
+    assert_eq!(output_error_exit_code(err).unwrap(), 141);
+    let err = io::Error::other("disk full");
+    assert_eq!(output_error_exit_code(err).unwrap_err().to_string(),
+               "Failed to write git output: disk full");
+}
+
+#[cfg(unix)]
+#[test]
+fn exit_code_after_output_error() {
+    use std::os::unix::process::ExitStatusExt;
+    let failed = ExitStatus::from_raw(1 << 8);
+    let broken_pipe = || Err(io::Error::from(io::ErrorKind::BrokenPipe));
+    assert_eq!(final_exit_code(failed, false, Ok(())).unwrap(), 1);
+    // git exited before its output could not be written anymore
+    assert_eq!(final_exit_code(failed, false, broken_pipe()).unwrap(), 1);
+    assert_eq!(final_exit_code(ExitStatus::from_raw(0), false, broken_pipe()).unwrap(), 0);
+    // git was killed, as it was still running
+    assert_eq!(final_exit_code(ExitStatus::from_raw(9), true, broken_pipe()).unwrap(),
+               BROKEN_PIPE_EXIT_CODE);
+    // an error of wslgit itself
+    assert!(final_exit_code(failed, false, Err(io::Error::other("disk full"))).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn exit_code_from_status() {
+    use std::os::unix::process::ExitStatusExt;
+    assert_eq!(status_to_exit_code(ExitStatus::from_raw(0)), 0);
+    assert_eq!(status_to_exit_code(ExitStatus::from_raw(2 << 8)), 2);
+    // terminated by SIGKILL
+    assert_eq!(status_to_exit_code(ExitStatus::from_raw(9)), 128 + 9);
+}
+
+#[cfg(windows)]
+#[test]
+fn exit_code_from_status() {
+    use std::os::windows::process::ExitStatusExt;
+    assert_eq!(status_to_exit_code(ExitStatus::from_raw(0)), 0);
+    assert_eq!(status_to_exit_code(ExitStatus::from_raw(2)), 2);
 }
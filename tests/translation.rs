@@ -0,0 +1,45 @@
+extern crate wslgit;
+
+use wslgit::{shell_escape, translate_path_to_unix, translate_path_to_win};
+
+
+#[test]
+fn path_round_trip() {
+    let unix_path = translate_path_to_unix("C:\\x\\y");
+    assert_eq!(unix_path, "/mnt/c/x/y");
+    assert_eq!(&*translate_path_to_win(unix_path.as_bytes()), b"C:\\x\\y");
+}
+
+#[test]
+fn native_separator_round_trip() {
+    // a path passed in comes out exactly the same again
+    let unix_path = translate_path_to_unix("C:\\a\\b");
+    assert_eq!(unix_path, "/mnt/c/a/b");
+    assert_eq!(&*translate_path_to_win(unix_path.as_bytes()), b"C:\\a\\b");
+    let unix_path = translate_path_to_unix("C:\\my repo\\.git");
+    assert_eq!(&*translate_path_to_win(unix_path.as_bytes()), b"C:\\my repo\\.git");
+}
+
+#[test]
+fn long_argument_round_trip() {
+    let unix_arg = translate_path_to_unix("--git-dir=D:\\repo\\.git");
+    assert_eq!(unix_arg, "--git-dir=/mnt/d/repo/.git");
+    assert_eq!(
+        &*translate_path_to_win(unix_arg.as_bytes()),
+        b"--git-dir=D:\\repo\\.git");
+}
+
+#[test]
+fn unix_path_round_trip() {
+    let win_path = translate_path_to_win(b"/mnt/c/some path/file.txt");
+    assert_eq!(&*win_path, b"C:\\some path\\file.txt");
+    let win_path = String::from_utf8(win_path.into_owned()).unwrap();
+    assert_eq!(translate_path_to_unix(&win_path), "/mnt/c/some path/file.txt");
+}
+
+#[test]
+fn escaped_path_argument() {
+    let unix_path = translate_path_to_unix("C:\\Users\\me\\a space.txt");
+    assert_eq!(shell_escape(unix_path.into_owned()), "'/mnt/c/Users/me/a space.txt'");
+    assert_eq!(shell_escape("/mnt/c/x/y".to_owned()), "/mnt/c/x/y");
+}